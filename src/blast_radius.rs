@@ -0,0 +1,173 @@
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use super::graph::TransitiveDependents;
+use super::ruff_util::{ruff_graph, Direction};
+use super::test_selection::{is_conftest, TestMatcher};
+
+/// What files are ranked by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RankBy {
+    Affected,
+    Tests,
+}
+
+impl FromStr for RankBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "affected" => Ok(RankBy::Affected),
+            "tests" => Ok(RankBy::Tests),
+            _ => Err(format!(
+                "unknown ranking '{}', expected 'affected' or 'tests'",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct BlastRadius {
+    file: String,
+    /// files that import it, directly or indirectly
+    affected: usize,
+    /// test files among those, plus itself if it's one, i.e. what `live --select tests`
+    /// would run after it changes
+    tests: usize,
+}
+
+/// Adds the conftests as dependencies of the tests they configure, the same way `live`
+/// treats them, since nothing imports them
+fn with_conftests(
+    graph: &HashMap<String, HashSet<String>>,
+    test_matcher: &TestMatcher,
+) -> HashMap<String, HashSet<String>> {
+    let mut graph = graph.clone();
+    let files = graph.keys().cloned().collect::<Vec<_>>();
+    for conftest in files.iter().filter(|f| is_conftest(f)) {
+        for test in test_matcher.conftest_dependents(conftest, files.iter()) {
+            graph.entry(test).or_default().insert(conftest.clone());
+        }
+    }
+    graph
+}
+
+pub(crate) fn blast_radii(
+    graph: &HashMap<String, HashSet<String>>,
+    test_matcher: &TestMatcher,
+    rank_by: RankBy,
+) -> Vec<BlastRadius> {
+    let graph = with_conftests(graph, test_matcher);
+    let dependents = TransitiveDependents::new(&graph);
+    let mut radii = graph
+        .keys()
+        .map(|file| BlastRadius {
+            file: file.clone(),
+            affected: dependents.count(file) - 1,
+            tests: dependents
+                .get(file)
+                .filter(|f| test_matcher.is_test_file(f))
+                .count(),
+        })
+        .collect::<Vec<_>>();
+    radii.sort_by(|a, b| {
+        let (a_key, b_key) = match rank_by {
+            RankBy::Affected => ((a.affected, a.tests), (b.affected, b.tests)),
+            RankBy::Tests => ((a.tests, a.affected), (b.tests, b.affected)),
+        };
+        b_key.cmp(&a_key).then_with(|| a.file.cmp(&b.file))
+    });
+    radii
+}
+
+pub(crate) fn blast_radius(options: super::BlastRadiusOptions) {
+    let graph = ruff_graph(false, Direction::Dependencies, None);
+    let test_matcher = TestMatcher::new(&options.test_patterns);
+    let mut radii = blast_radii(&graph, &test_matcher, options.rank_by);
+    radii.truncate(options.limit);
+
+    if options.json {
+        let radii = radii
+            .iter()
+            .map(|r| json!({"file": r.file, "affected": r.affected, "tests": r.tests}))
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&radii).unwrap());
+        return;
+    }
+    println!("{:>8}  {:>8}  file", "affected", "tests");
+    for radius in radii.iter() {
+        println!(
+            "{:>8}  {:>8}  {}",
+            radius.affected, radius.tests, radius.file
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> HashMap<String, HashSet<String>> {
+        HashMap::from([
+            ("src/util/bar.py".to_string(), HashSet::new()),
+            (
+                "src/util/foo.py".to_string(),
+                HashSet::from(["src/util/bar.py".to_string()]),
+            ),
+            (
+                "src/util/tests/test_foo.py".to_string(),
+                HashSet::from(["src/util/foo.py".to_string()]),
+            ),
+            (
+                "src/util/tests/test_bar.py".to_string(),
+                HashSet::from(["src/util/bar.py".to_string()]),
+            ),
+            ("src/util/tests/conftest.py".to_string(), HashSet::new()),
+            ("src/app.py".to_string(), HashSet::new()),
+            (
+                "src/main.py".to_string(),
+                HashSet::from(["src/app.py".to_string()]),
+            ),
+            (
+                "src/cli.py".to_string(),
+                HashSet::from(["src/app.py".to_string()]),
+            ),
+        ])
+    }
+
+    fn summary(radii: &[BlastRadius]) -> Vec<(&str, usize, usize)> {
+        radii
+            .iter()
+            .map(|r| (r.file.as_str(), r.affected, r.tests))
+            .collect()
+    }
+
+    #[test]
+    fn test_blast_radii() {
+        let test_matcher = TestMatcher::new("test_*.py");
+        assert_eq!(
+            summary(&blast_radii(&graph(), &test_matcher, RankBy::Affected)),
+            [
+                ("src/util/bar.py", 3, 2),
+                ("src/util/tests/conftest.py", 2, 2),
+                ("src/app.py", 2, 0),
+                ("src/util/foo.py", 1, 1),
+                ("src/util/tests/test_bar.py", 0, 1),
+                ("src/util/tests/test_foo.py", 0, 1),
+                ("src/cli.py", 0, 0),
+                ("src/main.py", 0, 0),
+            ]
+        );
+        assert_eq!(
+            summary(&blast_radii(&graph(), &test_matcher, RankBy::Tests))[..4],
+            [
+                ("src/util/bar.py", 3, 2),
+                ("src/util/tests/conftest.py", 2, 2),
+                ("src/util/foo.py", 1, 1),
+                ("src/util/tests/test_bar.py", 0, 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use serde_json::json;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+
+use super::cycle_detection::detect_cycles_in_graph;
+use super::imports::ImportLocator;
+use super::minimize_cycles::{canonical_cycle, minimize_cycle, missing_edges, parse_cycles};
+use super::ruff_util::{ruff_graph, to_module_graph, Direction};
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct CycleComparison {
+    common: BTreeSet<Vec<String>>,
+    only_ruff_tools: BTreeSet<Vec<String>>,
+    /// found by pylint in ruff's graph, but missed by ruff-tools
+    only_pylint: BTreeSet<Vec<String>>,
+    /// found by pylint, with imports that aren't in the graph compared against
+    false_positives: BTreeSet<Vec<String>>,
+}
+
+/// Compares the cycles `detect-cycles` finds in a graph of modules with pylint's, both
+/// minimized the same way
+pub(crate) fn compare_cycles(
+    graph: &HashMap<String, HashSet<String>>,
+    pylint_cycles: &[Vec<String>],
+) -> CycleComparison {
+    let owned = |cycle: Vec<&str>| cycle.into_iter().map(str::to_string).collect::<Vec<_>>();
+    let ruff_tools = detect_cycles_in_graph(graph)
+        .into_iter()
+        .map(owned)
+        .collect::<BTreeSet<_>>();
+
+    let mut comparison = CycleComparison::default();
+    let mut pylint = BTreeSet::new();
+    for cycle in pylint_cycles {
+        let cycle = cycle.iter().map(String::as_str).collect::<Vec<_>>();
+        if missing_edges(graph, &cycle).is_empty() {
+            pylint.insert(owned(canonical_cycle(&minimize_cycle(graph, &cycle))));
+        } else {
+            comparison
+                .false_positives
+                .insert(owned(canonical_cycle(&cycle)));
+        }
+    }
+    comparison.common = &ruff_tools & &pylint;
+    comparison.only_ruff_tools = &ruff_tools - &pylint;
+    comparison.only_pylint = &pylint - &ruff_tools;
+    comparison
+}
+
+pub(crate) fn compare(options: super::CompareCyclesOptions) -> Result<(), Box<dyn Error>> {
+    let contents = fs::read_to_string(&options.pylint_results)
+        .map_err(|e| format!("couldn't read {}: {}", options.pylint_results, e))?;
+    let (pylint_cycles, errors) = parse_cycles(&contents);
+    for error in &errors {
+        eprintln!("{}: {}", options.pylint_results, error);
+    }
+    let mut graph = ruff_graph(false, Direction::Dependencies, None);
+    if options.runtime_only {
+        graph = ImportLocator::default().runtime_graph(&graph);
+    }
+    let graph = to_module_graph(&graph);
+    let comparison = compare_cycles(&graph, &pylint_cycles);
+
+    if options.json {
+        let result = json!({
+            "common": comparison.common,
+            "only_ruff_tools": comparison.only_ruff_tools,
+            "only_pylint": comparison.only_pylint,
+            "false_positives": comparison.false_positives,
+        });
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        let false_positives = match options.runtime_only {
+            true => "Found by pylint, with imports that aren't in ruff's graph or don't run at import time",
+            false => "Found by pylint, with imports that aren't in ruff's graph",
+        };
+        let sections = [
+            ("Found by both", &comparison.common),
+            ("Only found by ruff-tools", &comparison.only_ruff_tools),
+            ("Only found by pylint", &comparison.only_pylint),
+            (false_positives, &comparison.false_positives),
+        ];
+        for (title, cycles) in sections {
+            println!("{}: {}", title, cycles.len());
+            for cycle in cycles {
+                println!("    {}", cycle.join(" -> "));
+            }
+        }
+    }
+
+    if !comparison.only_pylint.is_empty() {
+        return Err(format!(
+            "{} cycles found by pylint weren't found by ruff-tools",
+            comparison.only_pylint.len()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cycles(cycles: &[&[&str]]) -> BTreeSet<Vec<String>> {
+        cycles
+            .iter()
+            .map(|cycle| cycle.iter().map(|m| m.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_compare_cycles() {
+        let graph = HashMap::from([
+            ("a".to_string(), HashSet::from(["b".to_string()])),
+            (
+                "b".to_string(),
+                HashSet::from(["a".to_string(), "c".to_string()]),
+            ),
+            ("c".to_string(), HashSet::from(["d".to_string()])),
+            ("d".to_string(), HashSet::from(["c".to_string()])),
+            ("e".to_string(), HashSet::from(["f".to_string()])),
+            ("f".to_string(), HashSet::from(["g".to_string()])),
+            ("g".to_string(), HashSet::from(["e".to_string()])),
+        ]);
+        let pylint = [
+            // minimized the same way before comparing, and rotated
+            vec!["b".to_string(), "a".to_string()],
+            vec!["f".to_string(), "g".to_string(), "e".to_string()],
+            vec!["x".to_string(), "a".to_string()],
+        ];
+        assert_eq!(
+            compare_cycles(&graph, &pylint),
+            CycleComparison {
+                common: cycles(&[&["a", "b"], &["e", "f", "g"]]),
+                only_ruff_tools: cycles(&[&["c", "d"]]),
+                only_pylint: BTreeSet::new(),
+                false_positives: cycles(&[&["a", "x"]]),
+            }
+        );
+    }
+}
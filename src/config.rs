@@ -0,0 +1,537 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use toml::{Table, Value};
+
+/// Keys allowed at the top level, and in each command's table
+const KEYS: &[(Option<&str>, &[&str])] = &[
+    (None, &["ruff", "src"]),
+    (
+        Some("detect-cycles"),
+        &[
+            "format",
+            "baseline",
+            "exclude-edges",
+            "max-cycles",
+            "max-cycle-length",
+            "max-total-cycle-length",
+            "max-scc-size",
+        ],
+    ),
+    (Some("live"), &["cmd", "paths"]),
+    (Some("export"), &["format"]),
+    (Some("metrics"), &["format"]),
+    (Some("graph-diff"), &["format"]),
+];
+
+/// Settings from a `ruff-tools.toml`, or the `[tool.ruff-tools]` section of a
+/// `pyproject.toml`, with a table for each command, e.g. `[tool.ruff-tools.live]`;
+/// flags passed on the command line take precedence over them
+#[derive(Debug, Default)]
+pub(crate) struct Config {
+    /// the file the settings are from, whose directory paths in it are relative to
+    path: PathBuf,
+    /// the directory the settings were found from, which paths handed to ruff, or matched
+    /// against its graph, are rewritten relative to
+    cwd: PathBuf,
+    table: Table,
+}
+
+impl Config {
+    /// The settings for `dir`, from the closest directory, walking up, with either a
+    /// `ruff-tools.toml` or a `pyproject.toml` that has a `[tool.ruff-tools]` section,
+    /// the same way ruff finds its own; none if there isn't one
+    pub(crate) fn find(cwd: &Path) -> Result<Config, Box<dyn Error>> {
+        for dir in cwd.ancestors() {
+            let path = dir.join("ruff-tools.toml");
+            if path.is_file() {
+                let table = parse(&path)?;
+                return Config::new(path, cwd, table);
+            }
+            let path = dir.join("pyproject.toml");
+            if path.is_file() {
+                let section = parse(&path)?
+                    .get("tool")
+                    .and_then(|tool| tool.get("ruff-tools"))
+                    .cloned();
+                if let Some(section) = section {
+                    let Value::Table(table) = section else {
+                        return Err(format!(
+                            "[tool.ruff-tools] in {} must be a table",
+                            path.display()
+                        )
+                        .into());
+                    };
+                    return Config::new(path, cwd, table);
+                }
+            }
+        }
+        Ok(Config::default())
+    }
+
+    fn new(path: PathBuf, cwd: &Path, table: Table) -> Result<Config, Box<dyn Error>> {
+        let config = Config {
+            path,
+            cwd: cwd.to_path_buf(),
+            table,
+        };
+        for (section, keys) in KEYS {
+            let Some(table) = config.section(*section)? else {
+                continue;
+            };
+            for key in table.keys() {
+                let is_section = section.is_none() && KEYS.iter().any(|(s, _)| *s == Some(key));
+                if !is_section && !keys.contains(&key.as_str()) {
+                    return Err(config.invalid(*section, key, "unknown setting").into());
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    fn section(&self, section: Option<&str>) -> Result<Option<&Table>, String> {
+        let Some(section) = section else {
+            return Ok(Some(&self.table));
+        };
+        match self.table.get(section) {
+            None => Ok(None),
+            Some(Value::Table(table)) => Ok(Some(table)),
+            Some(_) => Err(format!(
+                "`{}` in {} must be a table",
+                section,
+                self.path.display()
+            )),
+        }
+    }
+
+    fn invalid(&self, section: Option<&str>, key: &str, problem: &str) -> String {
+        let key = match section {
+            Some(section) => format!("{}.{}", section, key),
+            None => key.to_string(),
+        };
+        format!("`{}` in {}: {}", key, self.path.display(), problem)
+    }
+
+    pub(crate) fn string(
+        &self,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<String>, String> {
+        match self.section(section)?.and_then(|table| table.get(key)) {
+            None => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.clone())),
+            Some(_) => Err(self.invalid(section, key, "expected a string")),
+        }
+    }
+
+    pub(crate) fn usize(&self, section: Option<&str>, key: &str) -> Result<Option<usize>, String> {
+        match self.section(section)?.and_then(|table| table.get(key)) {
+            None => Ok(None),
+            Some(Value::Integer(value)) if *value >= 0 => Ok(Some(*value as usize)),
+            Some(_) => Err(self.invalid(section, key, "expected a non-negative integer")),
+        }
+    }
+
+    pub(crate) fn strings(
+        &self,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<Vec<String>>, String> {
+        let Some(value) = self.section(section)?.and_then(|table| table.get(key)) else {
+            return Ok(None);
+        };
+        value
+            .as_array()
+            .and_then(|values| {
+                values
+                    .iter()
+                    .map(|value| value.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+            })
+            .map(Some)
+            .ok_or_else(|| self.invalid(section, key, "expected a list of strings"))
+    }
+
+    /// A string setting parsed the way the corresponding flag is
+    pub(crate) fn parse<T: FromStr<Err = String>>(
+        &self,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<T>, String> {
+        self.string(section, key)?
+            .map(|value| {
+                value
+                    .parse::<T>()
+                    .map_err(|e: String| self.invalid(section, key, &e))
+            })
+            .transpose()
+    }
+
+    /// A path setting, relative to the directory the settings are from
+    pub(crate) fn path(&self, section: Option<&str>, key: &str) -> Result<Option<String>, String> {
+        let dir = self.path.parent().unwrap_or(Path::new(""));
+        Ok(self
+            .string(section, key)?
+            .map(|path| dir.join(path).to_string_lossy().into_owned()))
+    }
+
+    /// A list of path settings, relative to the directory the settings are from, rewritten
+    /// relative to the current directory, which the paths in ruff's graph are relative to;
+    /// the current directory, and any directory above it, contain every file in the graph,
+    /// so they become the empty prefix
+    pub(crate) fn paths(
+        &self,
+        section: Option<&str>,
+        key: &str,
+    ) -> Result<Option<Vec<String>>, String> {
+        Ok(self.strings(section, key)?.map(|paths| {
+            paths
+                .iter()
+                .map(|path| match self.relative(path) {
+                    relative if is_above(&relative) => String::new(),
+                    relative => relative,
+                })
+                .collect()
+        }))
+    }
+
+    /// The `src` setting, as `paths`; modules can't be named relative to a root above the
+    /// current directory from in it, so those are an error
+    pub(crate) fn source_roots(&self) -> Result<Option<Vec<String>>, String> {
+        let Some(roots) = self.strings(None, "src")? else {
+            return Ok(None);
+        };
+        roots
+            .iter()
+            .map(|root| match self.relative(root) {
+                relative if is_above(&relative) => Err(self.invalid(
+                    None,
+                    "src",
+                    &format!(
+                        "'{}' is above the current directory; run ruff-tools from it instead",
+                        root
+                    ),
+                )),
+                relative => Ok(relative),
+            })
+            .collect::<Result<_, _>>()
+            .map(Some)
+    }
+
+    /// `path`, relative to the directory the settings are from, relative to the current
+    /// directory instead; empty for the current directory itself
+    fn relative(&self, path: &str) -> String {
+        let path = normalize(&self.path.parent().unwrap_or(Path::new("")).join(path));
+        let cwd = normalize(&self.cwd);
+        let common = path
+            .components()
+            .zip(cwd.components())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let relative = cwd
+            .components()
+            .skip(common)
+            .map(|_| Component::ParentDir)
+            .chain(path.components().skip(common))
+            .collect::<PathBuf>();
+        relative.to_string_lossy().into_owned()
+    }
+
+    /// The `ruff` to run: a path relative to the settings if it has a directory in it,
+    /// otherwise a command looked up on `PATH`
+    pub(crate) fn ruff(&self) -> Result<Option<String>, String> {
+        match self.string(None, "ruff")? {
+            Some(ruff) if ruff.contains('/') => self.path(None, "ruff"),
+            ruff => Ok(ruff),
+        }
+    }
+
+    pub(crate) fn apply_cycle_detection(
+        &self,
+        options: &mut super::CycleDetectionOptions,
+    ) -> Result<(), String> {
+        let section = Some("detect-cycles");
+        if options.format.is_none() {
+            options.format = self.parse(section, "format")?;
+        }
+        if options.baseline.is_none() {
+            options.baseline = self.path(section, "baseline")?;
+        }
+        if options.exclude_edges.is_empty() {
+            // either side of an edge may be a file, rather than a module
+            let resolve = |name: &str| match name.trim() {
+                name if name.contains('/') || name.ends_with(".py") => self.relative(name),
+                name => name.to_string(),
+            };
+            options.exclude_edges = self
+                .strings(section, "exclude-edges")?
+                .unwrap_or_default()
+                .iter()
+                .map(|edge| match edge.split_once("->") {
+                    Some((importer, imported)) => {
+                        format!("{} -> {}", resolve(importer), resolve(imported))
+                    }
+                    None => edge.clone(),
+                })
+                .collect();
+        }
+        for (limit, key) in [
+            (&mut options.max_cycles, "max-cycles"),
+            (&mut options.max_cycle_length, "max-cycle-length"),
+            (
+                &mut options.max_total_cycle_length,
+                "max-total-cycle-length",
+            ),
+            (&mut options.max_scc_size, "max-scc-size"),
+        ] {
+            if limit.is_none() {
+                *limit = self.usize(section, key)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn apply_live(&self, options: &mut super::LiveOptions) -> Result<(), String> {
+        let section = Some("live");
+        if options.cmd.is_empty() {
+            options.cmd = self.strings(section, "cmd")?.unwrap_or_default();
+        }
+        if options.paths.is_empty() {
+            options.paths = self.paths(section, "paths")?.unwrap_or_default().join(",");
+        }
+        Ok(())
+    }
+
+    pub(crate) fn apply_export(&self, options: &mut super::ExportOptions) -> Result<(), String> {
+        if options.format.is_none() {
+            options.format = self.parse(Some("export"), "format")?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn apply_metrics(&self, options: &mut super::MetricsOptions) -> Result<(), String> {
+        if options.format.is_none() {
+            options.format = self.parse(Some("metrics"), "format")?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn apply_graph_diff(
+        &self,
+        options: &mut super::GraphDiffOptions,
+    ) -> Result<(), String> {
+        if options.format.is_none() {
+            options.format = self.parse(Some("graph-diff"), "format")?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether a relative path from `relative` only goes up, to a directory above the current one
+fn is_above(relative: &str) -> bool {
+    !relative.is_empty()
+        && Path::new(relative)
+            .components()
+            .all(|c| c == Component::ParentDir)
+}
+
+/// `path` with `.` and `..` resolved lexically, the way the shell would
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn parse(path: &Path) -> Result<Table, Box<dyn Error>> {
+    fs::read_to_string(path)?
+        .parse::<Table>()
+        .map_err(|e| format!("couldn't parse {}: {}", path.display(), e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    fn config(source: &str) -> Result<Config, Box<dyn Error>> {
+        Config::new(
+            PathBuf::from("/repo/ruff-tools.toml"),
+            Path::new("/repo"),
+            source.parse()?,
+        )
+    }
+
+    #[test]
+    fn test_find() {
+        let root = std::env::temp_dir().join(format!("ruff-tools-config-{}", std::process::id()));
+        let nested = root.join("project/src/pkg");
+        fs::create_dir_all(&nested).unwrap();
+        assert!(Config::find(&nested).unwrap().table.is_empty());
+
+        // a pyproject.toml without a section doesn't count
+        fs::write(root.join("project/pyproject.toml"), "[tool.ruff]\n").unwrap();
+        fs::write(
+            root.join("pyproject.toml"),
+            "[tool.ruff-tools]\nruff = \"bin/ruff\"\n",
+        )
+        .unwrap();
+        let config = Config::find(&nested).unwrap();
+        assert_eq!(config.path, root.join("pyproject.toml"));
+        assert_eq!(
+            config.ruff().unwrap(),
+            Some(root.join("bin/ruff").to_string_lossy().into_owned())
+        );
+
+        fs::write(root.join("project/ruff-tools.toml"), "src = [\"src\"]\n").unwrap();
+        let config = Config::find(&nested).unwrap();
+        assert_eq!(config.path, root.join("project/ruff-tools.toml"));
+        assert_eq!(
+            config.strings(None, "src").unwrap(),
+            Some(vec!["src".to_string()])
+        );
+        assert_eq!(config.ruff().unwrap(), None);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_settings() {
+        let config = config(
+            r#"
+ruff = "ruff"
+
+[detect-cycles]
+format = "sarif"
+baseline = "cycles.txt"
+max-cycles = 3
+
+[live]
+cmd = ["pytest", "-x"]
+paths = ["src", "tests"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.ruff().unwrap(), Some("ruff".to_string()));
+        assert_eq!(
+            config.parse(Some("detect-cycles"), "format").unwrap(),
+            Some(super::super::cycle_detection::CycleFormat::Sarif)
+        );
+        assert_eq!(
+            config.path(Some("detect-cycles"), "baseline").unwrap(),
+            Some("/repo/cycles.txt".to_string())
+        );
+        assert_eq!(config.strings(Some("export"), "format").unwrap(), None);
+        assert_eq!(
+            config.usize(Some("detect-cycles"), "max-cycles").unwrap(),
+            Some(3)
+        );
+        assert_eq!(
+            config.strings(Some("live"), "paths").unwrap(),
+            Some(vec!["src".to_string(), "tests".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_relative_paths() {
+        let source = r#"
+src = ["src", "../lib", "."]
+
+[detect-cycles]
+exclude-edges = ["app.models -> src/app/signals.py"]
+
+[live]
+paths = ["sub/tests", "sub", "."]
+"#;
+        // from the directory of the settings, paths are as written, and that directory
+        // itself is the empty prefix every path in ruff's graph starts with
+        let config = config(source).unwrap();
+        assert_eq!(
+            config.source_roots().unwrap(),
+            Some(vec!["src".to_string(), "../lib".to_string(), String::new()])
+        );
+        assert_eq!(
+            config.paths(Some("live"), "paths").unwrap(),
+            Some(vec![
+                "sub/tests".to_string(),
+                "sub".to_string(),
+                String::new()
+            ])
+        );
+
+        let nested = Config::new(
+            PathBuf::from("/repo/ruff-tools.toml"),
+            Path::new("/repo/sub"),
+            source.parse().unwrap(),
+        )
+        .unwrap();
+        // a directory above the current one contains all of it too
+        assert_eq!(
+            nested.paths(Some("live"), "paths").unwrap(),
+            Some(vec!["tests".to_string(), String::new(), String::new()])
+        );
+        // but modules can't be named relative to it
+        assert_eq!(
+            nested.source_roots().unwrap_err(),
+            "`src` in /repo/ruff-tools.toml: '.' is above the current directory; run ruff-tools from it instead"
+        );
+
+        let mut options = super::super::CycleDetectionOptions::from_iter(["detect-cycles"]);
+        nested.apply_cycle_detection(&mut options).unwrap();
+        assert_eq!(
+            options.exclude_edges,
+            ["app.models -> ../src/app/signals.py"]
+        );
+
+        let nested = Config::new(
+            PathBuf::from("/repo/ruff-tools.toml"),
+            Path::new("/repo/sub"),
+            "src = [\"src\", \"../lib\"]".parse().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            nested.source_roots().unwrap(),
+            Some(vec!["../src".to_string(), "../../lib".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        let error = |source: &str| config(source).err().unwrap().to_string();
+        assert_eq!(
+            error("rufff = \"ruff\""),
+            "`rufff` in /repo/ruff-tools.toml: unknown setting"
+        );
+        assert_eq!(
+            error("[live]\npath = []"),
+            "`live.path` in /repo/ruff-tools.toml: unknown setting"
+        );
+        assert_eq!(
+            error("live = 1"),
+            "`live` in /repo/ruff-tools.toml must be a table"
+        );
+
+        let config = config("src = \"src\"\n[detect-cycles]\nformat = \"xml\"\n").unwrap();
+        assert_eq!(
+            config.strings(None, "src").unwrap_err(),
+            "`src` in /repo/ruff-tools.toml: expected a list of strings"
+        );
+        assert_eq!(
+            config
+                .parse::<super::super::cycle_detection::CycleFormat>(
+                    Some("detect-cycles"),
+                    "format"
+                )
+                .unwrap_err(),
+            "`detect-cycles.format` in /repo/ruff-tools.toml: unknown format 'xml', expected 'text' or 'sarif'"
+        );
+    }
+}
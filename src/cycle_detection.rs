@@ -1,30 +1,283 @@
 extern crate alloc;
 
+use serde_json::{json, Value};
 use std::cmp;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::str::FromStr;
 
-pub(crate) fn detect_cycles() {
-    let graph = super::ruff_util::ruff_graph(false, false, None);
-    let cycles = detect_cycles_in_graph(&graph);
+use super::graph::strongly_connected_components;
+use super::imports::ImportLocator;
+use super::minimize_cycles::canonical_cycle;
+use super::ruff_util::{path_to_module, Direction};
+use super::sarif;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CycleFormat {
+    #[default]
+    Text,
+    Sarif,
+}
+
+impl FromStr for CycleFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(CycleFormat::Text),
+            "sarif" => Ok(CycleFormat::Sarif),
+            _ => Err(format!(
+                "unknown format '{}', expected 'text' or 'sarif'",
+                s
+            )),
+        }
+    }
+}
+
+/// Removes the imports given as `importer -> imported`, where each side is a file or a
+/// module, from a graph of files
+fn exclude_edges(
+    graph: &mut HashMap<String, HashSet<String>>,
+    edges: &[String],
+) -> Result<(), String> {
+    let matches = |file: &str, name: &str| file == name || path_to_module(file) == name;
+    for edge in edges {
+        let Some((importer, imported)) = edge.split_once("->") else {
+            return Err(format!(
+                "invalid edge '{}', expected 'importer -> imported'",
+                edge
+            ));
+        };
+        let (importer, imported) = (importer.trim(), imported.trim());
+        for (file, dependencies) in graph.iter_mut() {
+            if matches(file, importer) {
+                dependencies.retain(|dependency| !matches(dependency, imported));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The cycles in a baseline file, one per line as `detect-cycles` prints them
+fn read_baseline(path: &str) -> Result<HashSet<String>, Box<dyn Error>> {
+    let baseline =
+        fs::read_to_string(path).map_err(|e| format!("couldn't read baseline {}: {}", path, e))?;
+    Ok(baseline
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let cycle = line.split("->").map(str::trim).collect::<Vec<_>>();
+            canonical_cycle(&cycle).join(" -> ")
+        })
+        .collect())
+}
+
+pub(crate) fn detect_cycles(options: super::CycleDetectionOptions) -> Result<(), Box<dyn Error>> {
+    let mut graph = super::ruff_util::ruff_graph(false, Direction::Dependencies, None);
+    let mut locator = ImportLocator::default();
+    if options.runtime_only {
+        graph = locator.runtime_graph(&graph);
+    }
+    exclude_edges(&mut graph, &options.exclude_edges)?;
+    let unused_suppressions = locator.suppress(&mut graph);
+    let mut cycles = detect_cycles_in_graph(&graph);
+    // limits cover every cycle, so they cap the total even with a baseline
+    let stats = CycleStats {
+        cycles: cycles.len(),
+        total_length: cycles.iter().map(|c| c.len()).sum(),
+        longest: cycles.iter().map(|c| c.len()).max().unwrap_or(0),
+        largest_scc: strongly_connected_components(&graph)
+            .iter()
+            .map(|scc| scc.len())
+            .filter(|size| *size > 1)
+            .max()
+            .unwrap_or(0),
+    };
+
+    let mut known = 0;
+    if let Some(baseline) = &options.baseline {
+        let mut lines = cycles.iter().map(|c| c.join(" -> ")).collect::<Vec<_>>();
+        if options.write_baseline {
+            lines.sort();
+            fs::write(
+                baseline,
+                lines.iter().map(|l| format!("{}\n", l)).collect::<String>(),
+            )?;
+            println!("Wrote {} cycles to {}", lines.len(), baseline);
+            return Ok(());
+        }
+        let accepted = read_baseline(baseline)?;
+        let resolved = accepted.len() - lines.iter().filter(|l| accepted.contains(*l)).count();
+        if resolved > 0 {
+            eprintln!(
+                "{} cycles in {} no longer exist; update it with --write-baseline",
+                resolved, baseline
+            );
+        }
+        cycles.retain(|c| !accepted.contains(&c.join(" -> ")));
+        known = lines.len() - cycles.len();
+    }
+
+    if options.format == Some(CycleFormat::Sarif) {
+        let mut cycles = cycles.into_iter().collect::<Vec<_>>();
+        cycles.sort();
+        let results = cycles
+            .iter()
+            .map(|c| cycle_result(c, &mut locator))
+            .chain(unused_suppressions.iter().map(|(file, line)| {
+                json!({
+                    "ruleId": sarif::UNUSED_SUPPRESSION.id,
+                    "level": "warning",
+                    "message": {"text": "Unused cycle suppression"},
+                    "locations": [sarif::location(file, *line)],
+                })
+            }))
+            .collect();
+        let log = sarif::log(&[sarif::CYCLIC_IMPORT, sarif::UNUSED_SUPPRESSION], results);
+        println!("{}", serde_json::to_string_pretty(&log).unwrap());
+        return check(&options, &stats, cycles.len());
+    }
     for cycle in &cycles {
         println!("{}", cycle.join(" -> "));
+        for import in locator.describe_cycle(cycle) {
+            println!("    {}", import);
+        }
     }
     println!();
     println!("Summary:");
-    println!("# cycles          : {}", cycles.len());
-    println!(
-        "total cycle length: {}",
-        cycles.iter().map(|c| c.len()).sum::<usize>()
-    );
-
-    println!(
-        "longest cycle     : {}",
-        cycles.iter().map(|c| c.len()).max().unwrap()
-    );
+    println!("# cycles          : {}", stats.cycles);
+    if options.baseline.is_some() {
+        println!("# in the baseline : {}", known);
+    }
+    println!("total cycle length: {}", stats.total_length);
+    println!("longest cycle     : {}", stats.longest);
+    println!("largest SCC       : {}", stats.largest_scc);
 
     // print potentially most problematic edges (which show up in many cycles)
     // breaking these edges _might_ help resolve many cycles at once
+    let edge_frequencies = edge_frequencies(&cycles);
+    let mut hash_vec: Vec<_> = edge_frequencies.iter().collect();
+    hash_vec.sort_by(|a, b| b.1.cmp(a.1));
+    println!("Most frequently-appearing imports in cycles:");
+    for (edge, frequency) in hash_vec.iter().take(cmp::min(hash_vec.len(), 5)) {
+        println!("{} {} -> {}", frequency, edge.0, edge.1);
+    }
+    println!("Removing these imports \x1b[3mmight\x1b[0m help resolve several cyclic dependencies");
+
+    if !unused_suppressions.is_empty() {
+        println!();
+        println!("Unused cycle suppressions (the imports aren't part of any cycle):");
+        for (file, line) in unused_suppressions {
+            println!("{}:{}", file, line);
+        }
+    }
+    check(&options, &stats, cycles.len())
+}
+
+/// The numbers in the summary, which `--max-*` options set limits for, counting cycles
+/// in the baseline too
+struct CycleStats {
+    cycles: usize,
+    total_length: usize,
+    longest: usize,
+    /// modules in the largest group that all import each other, directly or indirectly
+    largest_scc: usize,
+}
+
+/// Fails when there are `new` cycles that aren't in the baseline, or the cycles go over
+/// any of the limits
+fn check(
+    options: &super::CycleDetectionOptions,
+    stats: &CycleStats,
+    new: usize,
+) -> Result<(), Box<dyn Error>> {
+    let mut errors = Vec::new();
+    if let Some(baseline) = options.baseline.as_ref().filter(|_| new > 0) {
+        errors.push(format!(
+            "{} cycles aren't in the baseline {}",
+            new, baseline
+        ));
+    }
+    let limits = [
+        (
+            "number of cycles",
+            stats.cycles,
+            options.max_cycles,
+            "--max-cycles",
+        ),
+        (
+            "longest cycle",
+            stats.longest,
+            options.max_cycle_length,
+            "--max-cycle-length",
+        ),
+        (
+            "total cycle length",
+            stats.total_length,
+            options.max_total_cycle_length,
+            "--max-total-cycle-length",
+        ),
+        (
+            "largest SCC",
+            stats.largest_scc,
+            options.max_scc_size,
+            "--max-scc-size",
+        ),
+    ];
+    for (name, value, limit, option) in limits {
+        if let Some(limit) = limit.filter(|limit| value > *limit) {
+            errors.push(format!(
+                "{} is {}, over the limit of {} ({})",
+                name, value, limit, option
+            ));
+        }
+    }
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors.join("\n").into()),
+    }
+}
+
+/// A SARIF result located at the cycle's first import, with every other import in the
+/// cycle as a related location
+fn cycle_result(cycle: &[&str], locator: &mut ImportLocator) -> Value {
+    let next = |i: usize| cycle[(i + 1) % cycle.len()];
+    let import = |i: usize| format!("{} imports {}", cycle[i], next(i));
+    // the file itself when the import statement can't be found
+    let mut line = |i: usize| {
+        locator
+            .lines(cycle[i], next(i))
+            .first()
+            .copied()
+            .unwrap_or(1)
+    };
+    let related = (1..cycle.len())
+        .map(|i| {
+            let mut location = sarif::location(cycle[i], line(i));
+            location["id"] = json!(i);
+            location["message"] = json!({"text": import(i)});
+            location
+        })
+        .collect::<Vec<_>>();
+    let mut path = cycle.to_vec();
+    path.push(cycle[0]);
+    json!({
+        "ruleId": sarif::CYCLIC_IMPORT.id,
+        "level": "warning",
+        "message": {"text": format!("Import cycle: {}", path.join(" -> "))},
+        "locations": [sarif::location(cycle[0], line(0))],
+        "relatedLocations": related,
+        "partialFingerprints": {"importCycle/v1": cycle.join(" -> ")},
+    })
+}
+
+/// Counts how many of `cycles` each edge appears in
+pub(crate) fn edge_frequencies<'a>(
+    cycles: &HashSet<Vec<&'a str>>,
+) -> HashMap<(&'a str, &'a str), u32> {
     let mut edge_frequencies: HashMap<(&str, &str), u32> = HashMap::new();
     for cycle in cycles {
         for i in 0..cycle.len() {
@@ -32,17 +285,12 @@ pub(crate) fn detect_cycles() {
             *edge_frequencies.entry(edge).or_default() += 1;
         }
     }
-
-    let mut hash_vec: Vec<_> = edge_frequencies.iter().collect();
-    hash_vec.sort_by(|a, b| b.1.cmp(a.1));
-    println!("Most frequently-appearing imports in cycles:");
-    for (edge, frequency) in hash_vec.iter().take(cmp::min(hash_vec.len(), 5)) {
-        println!("{} {} -> {}", frequency, edge.0, edge.1);
-    }
-    println!("Removing these imports \x1b[3mmight\x1b[0m help resolve several cyclic dependencies")
+    edge_frequencies
 }
 
-fn detect_cycles_in_graph(graph: &HashMap<String, HashSet<String>>) -> HashSet<Vec<&str>> {
+pub(crate) fn detect_cycles_in_graph(
+    graph: &HashMap<String, HashSet<String>>,
+) -> HashSet<Vec<&str>> {
     let mut cycles = HashSet::new();
     for vertex in graph.keys() {
         cycles.extend(get_cycles_from_vertex(graph, vertex));
@@ -109,6 +357,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_cycle_result() {
+        let result = cycle_result(&["a.py", "b.py", "c.py"], &mut ImportLocator::default());
+        assert_eq!(
+            result["message"]["text"],
+            "Import cycle: a.py -> b.py -> c.py -> a.py"
+        );
+        assert_eq!(
+            result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "a.py"
+        );
+        assert_eq!(
+            result["relatedLocations"],
+            json!([
+                {
+                    "id": 1,
+                    "message": {"text": "b.py imports c.py"},
+                    "physicalLocation": {
+                        "artifactLocation": {"uri": "b.py"},
+                        "region": {"startLine": 1},
+                    },
+                },
+                {
+                    "id": 2,
+                    "message": {"text": "c.py imports a.py"},
+                    "physicalLocation": {
+                        "artifactLocation": {"uri": "c.py"},
+                        "region": {"startLine": 1},
+                    },
+                },
+            ])
+        );
+    }
+
     /// has many cycles - however, since the algorithm isn't guaranteed to find all
     /// of them, we make sure we find at least a certain number - this is deterministic,
     /// but not reasonable pre-calculable
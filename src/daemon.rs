@@ -0,0 +1,395 @@
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
+use std::error::Error;
+
+use super::cycle_detection::detect_cycles_in_graph;
+use super::live::{normalize_path, LiveGraph};
+
+/// Answers a JSON-RPC 2.0 request against the graph; file paths in params are normalized
+/// against `cwd`, the directory the graph was built in
+fn handle_request(graph: &LiveGraph, cwd: &str, request: &Value) -> Value {
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let (Some(method), params) = (
+        request.get("method").and_then(Value::as_str),
+        request.get("params").cloned().unwrap_or(json!({})),
+    ) else {
+        return error_response(id, -32600, "invalid request".to_string());
+    };
+
+    let path_param = |name: &str| -> Result<String, String> {
+        let path = params
+            .get(name)
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("missing string param '{}'", name))?;
+        let path = normalize_path(cwd, path);
+        if graph.contains(&path) {
+            Ok(path)
+        } else {
+            Err(format!("unknown file '{}'", path))
+        }
+    };
+    let sorted = |files: Option<&HashSet<String>>| -> Value {
+        let mut files = files.into_iter().flatten().collect::<Vec<_>>();
+        files.sort();
+        json!(files)
+    };
+
+    let result = match method {
+        "affected_files" => params
+            .get("paths")
+            .and_then(Value::as_array)
+            .map(|paths| {
+                let paths = paths
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(|p| normalize_path(cwd, p))
+                    .collect::<Vec<_>>();
+                let affected_files = graph
+                    .affected_files(&paths)
+                    .into_iter()
+                    .filter(|p| graph.contains(p))
+                    .collect::<HashSet<_>>();
+                sorted(Some(&affected_files))
+            })
+            .ok_or_else(|| "missing array param 'paths'".to_string()),
+        "dependents" => path_param("path").map(|path| sorted(graph.dependents().get(&path))),
+        "dependencies" => path_param("path").map(|path| sorted(graph.dependencies().get(&path))),
+        "path_between" => path_param("from").and_then(|from| {
+            path_param("to").map(|to| json!(shortest_path(graph.dependencies(), &from, &to)))
+        }),
+        "cycles" => path_param("path").map(|path| json!(cycles_containing(graph, &path))),
+        _ => return error_response(id, -32601, format!("unknown method '{}'", method)),
+    };
+
+    match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(message) => error_response(id, -32602, message),
+    }
+}
+
+fn error_response(id: Value, code: i32, message: String) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+/// The shortest chain of imports from `from` to `to`, as a BFS over the dependencies graph
+fn shortest_path(
+    dependencies: &HashMap<String, HashSet<String>>,
+    from: &str,
+    to: &str,
+) -> Option<Vec<String>> {
+    let mut predecessors: HashMap<&str, &str> = HashMap::new();
+    let mut queue: VecDeque<&str> = VecDeque::from([from]);
+    while let Some(file) = queue.pop_front() {
+        if file == to {
+            let mut path = vec![to.to_string()];
+            let mut current = to;
+            while current != from {
+                current = predecessors[current];
+                path.push(current.to_string());
+            }
+            path.reverse();
+            return Some(path);
+        }
+        for dependency in dependencies.get(file).into_iter().flatten() {
+            if dependency != from && !predecessors.contains_key(dependency.as_str()) {
+                predecessors.insert(dependency, file);
+                queue.push_back(dependency);
+            }
+        }
+    }
+    None
+}
+
+/// The cycles through `path`; only its strongly-connected component can contain any,
+/// so cycle detection is limited to that
+fn cycles_containing(graph: &LiveGraph, path: &str) -> Vec<Vec<String>> {
+    let dependents = graph.affected_files(&[path.to_string()]);
+    let mut component = HashSet::from([path.to_string()]);
+    let mut queue = VecDeque::from([path.to_string()]);
+    while let Some(file) = queue.pop_front() {
+        for dependency in graph.dependencies().get(&file).into_iter().flatten() {
+            if dependents.contains(dependency) && component.insert(dependency.clone()) {
+                queue.push_back(dependency.clone());
+            }
+        }
+    }
+
+    let subgraph = component
+        .iter()
+        .map(|file| {
+            let dependencies = graph.dependencies().get(file).into_iter().flatten();
+            (
+                file.clone(),
+                dependencies
+                    .filter(|d| component.contains(*d))
+                    .cloned()
+                    .collect::<HashSet<_>>(),
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    let mut cycles = detect_cycles_in_graph(&subgraph)
+        .into_iter()
+        .filter(|cycle| cycle.contains(&path))
+        .map(|cycle| cycle.into_iter().map(str::to_string).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    cycles.sort_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)));
+    cycles
+}
+
+fn cwd() -> Result<String, Box<dyn Error>> {
+    Ok(env::current_dir()?.to_string_lossy().into_owned() + "/")
+}
+
+#[cfg(unix)]
+pub(crate) fn run_daemon(options: super::DaemonOptions) -> Result<(), Box<dyn Error>> {
+    use super::live::ChangeTracker;
+    use notify::{RecursiveMode, Watcher};
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixListener;
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+
+    let cwd = cwd()?;
+    println!("\x1b[93mConstructing initial graph ...\x1b[0m");
+    let graph = Arc::new(Mutex::new(LiveGraph::construct()));
+
+    // the graph is kept up to date the same way as in live mode
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(env::current_dir()?.as_path(), RecursiveMode::Recursive)?;
+    let watched_graph = graph.clone();
+    let mut tracker = ChangeTracker::new(&cwd);
+    thread::spawn(move || loop {
+        let res = match tracker.timeout() {
+            None => match rx.recv() {
+                Ok(res) => res,
+                Err(_) => break,
+            },
+            Some(timeout) => match rx.recv_timeout(timeout) {
+                Ok(res) => res,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    let mut graph = watched_graph.lock().unwrap();
+                    if let Some(change) = tracker.flush().and_then(|c| graph.relevant_change(c)) {
+                        graph.apply(change);
+                    }
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            },
+        };
+        let Ok(event) = res else { continue };
+        let mut graph = watched_graph.lock().unwrap();
+        for change in tracker.changes(event) {
+            if let Some(change) = graph.relevant_change(change) {
+                graph.apply(change);
+            }
+        }
+    });
+
+    let _ = std::fs::remove_file(&options.socket);
+    let listener = UnixListener::bind(&options.socket)?;
+    println!(
+        "\x1b[93mServing queries on {}! Ctrl-C to quit.\x1b[0m",
+        options.socket
+    );
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        let graph = graph.clone();
+        let cwd = cwd.clone();
+        // one request per line, one response per line
+        thread::spawn(move || {
+            let mut writer = match stream.try_clone() {
+                Ok(writer) => writer,
+                Err(_) => return,
+            };
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else { break };
+                let response = match serde_json::from_str::<Value>(&line) {
+                    Ok(request) => handle_request(&graph.lock().unwrap(), &cwd, &request),
+                    Err(e) => error_response(Value::Null, -32700, e.to_string()),
+                };
+                if writeln!(writer, "{}", response).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn run_daemon(_options: super::DaemonOptions) -> Result<(), Box<dyn Error>> {
+    Err("the daemon is only supported on Unix".into())
+}
+
+/// Sends a single query to the daemon and prints the result; returns the exit code
+#[cfg(unix)]
+pub(crate) fn run_query(options: super::QueryOptions) -> Result<i32, Box<dyn Error>> {
+    use super::Query;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    // relative paths are sent as absolute ones, since the daemon may run elsewhere
+    let cwd = cwd()?;
+    let absolute = |p: &String| {
+        if p.starts_with('/') {
+            p.clone()
+        } else {
+            cwd.clone() + p
+        }
+    };
+    let (method, params) = match &options.query {
+        Query::AffectedFiles { paths } => (
+            "affected_files",
+            json!({"paths": paths.iter().map(absolute).collect::<Vec<_>>()}),
+        ),
+        Query::Dependents { path } => ("dependents", json!({"path": absolute(path)})),
+        Query::Dependencies { path } => ("dependencies", json!({"path": absolute(path)})),
+        Query::PathBetween { from, to } => (
+            "path_between",
+            json!({"from": absolute(from), "to": absolute(to)}),
+        ),
+        Query::Cycles { path } => ("cycles", json!({"path": absolute(path)})),
+    };
+
+    let mut stream = UnixStream::connect(&options.socket)
+        .map_err(|e| format!("couldn't connect to {}: {}", options.socket, e))?;
+    let request = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params});
+    writeln!(stream, "{}", request)?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    let response = serde_json::from_str::<Value>(&line)?;
+
+    if let Some(error) = response.get("error") {
+        eprintln!("{}", error["message"].as_str().unwrap_or("unknown error"));
+        return Ok(1);
+    }
+    let result = &response["result"];
+    if options.json {
+        println!("{}", result);
+        return Ok(0);
+    }
+    match &options.query {
+        Query::PathBetween { .. } => match result.as_array() {
+            Some(path) => println!("{}", join(path)),
+            None => println!("No import path"),
+        },
+        Query::Cycles { .. } => {
+            for cycle in result.as_array().into_iter().flatten() {
+                println!("{}", join(cycle.as_array().unwrap()));
+            }
+        }
+        _ => {
+            for file in result.as_array().into_iter().flatten() {
+                println!("{}", file.as_str().unwrap_or_default());
+            }
+        }
+    }
+    Ok(0)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn run_query(_options: super::QueryOptions) -> Result<i32, Box<dyn Error>> {
+    Err("the daemon is only supported on Unix".into())
+}
+
+fn join(files: &[Value]) -> String {
+    files
+        .iter()
+        .filter_map(Value::as_str)
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> LiveGraph {
+        LiveGraph::from_dependencies(&[
+            ("a.py", &["b.py"]),
+            ("b.py", &["c.py", "d.py"]),
+            ("c.py", &["a.py"]),
+            ("d.py", &["e.py"]),
+            ("e.py", &["b.py"]),
+            ("f.py", &["a.py"]),
+        ])
+    }
+
+    fn request(method: &str, params: Value) -> Value {
+        handle_request(
+            &graph(),
+            "/repo/",
+            &json!({"jsonrpc": "2.0", "id": 7, "method": method, "params": params}),
+        )
+    }
+
+    #[test]
+    fn test_handle_request() {
+        assert_eq!(
+            request("affected_files", json!({"paths": ["./d.py"]})),
+            json!({"jsonrpc": "2.0", "id": 7, "result": ["a.py", "b.py", "c.py", "d.py", "e.py", "f.py"]})
+        );
+        assert_eq!(
+            request("dependents", json!({"path": "/repo/a.py"}))["result"],
+            json!(["c.py", "f.py"])
+        );
+        assert_eq!(
+            request("dependencies", json!({"path": "b.py"}))["result"],
+            json!(["c.py", "d.py"])
+        );
+        assert_eq!(
+            request("path_between", json!({"from": "f.py", "to": "e.py"}))["result"],
+            json!(["f.py", "a.py", "b.py", "d.py", "e.py"])
+        );
+        assert_eq!(
+            request("path_between", json!({"from": "a.py", "to": "f.py"}))["result"],
+            Value::Null
+        );
+        assert_eq!(
+            request("cycles", json!({"path": "a.py"}))["result"],
+            json!([["a.py", "b.py", "c.py"]])
+        );
+        assert_eq!(
+            request("cycles", json!({"path": "f.py"}))["result"],
+            json!([])
+        );
+    }
+
+    #[test]
+    fn test_handle_request_errors() {
+        assert_eq!(request("nope", json!({}))["error"]["code"], -32601);
+        assert_eq!(
+            request("dependents", json!({"path": "z.py"}))["error"],
+            json!({"code": -32602, "message": "unknown file 'z.py'"})
+        );
+        assert_eq!(
+            request("path_between", json!({"from": "a.py"}))["error"]["code"],
+            -32602
+        );
+        assert_eq!(
+            handle_request(&graph(), "/repo/", &json!({"id": 1}))["error"]["code"],
+            -32600
+        );
+    }
+
+    #[test]
+    fn test_shortest_path() {
+        let graph = graph();
+        assert_eq!(
+            shortest_path(graph.dependencies(), "a.py", "a.py"),
+            Some(vec!["a.py".to_string()])
+        );
+        assert_eq!(
+            shortest_path(graph.dependencies(), "c.py", "e.py"),
+            Some(vec![
+                "c.py".to_string(),
+                "a.py".to_string(),
+                "b.py".to_string(),
+                "d.py".to_string(),
+                "e.py".to_string()
+            ])
+        );
+    }
+}
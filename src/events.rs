@@ -0,0 +1,141 @@
+use serde_json::{json, Value};
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::sync::{Arc, Mutex};
+#[cfg(unix)]
+use std::thread;
+#[cfg(unix)]
+use std::time::Duration;
+
+/// Format of the machine-readable event stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventFormat {
+    Ndjson,
+}
+
+impl FromStr for EventFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ndjson" => Ok(EventFormat::Ndjson),
+            _ => Err(format!("unknown event format '{}', expected 'ndjson'", s)),
+        }
+    }
+}
+
+enum Target {
+    Stdout,
+    /// every client connected to the socket gets every event from when it connected
+    #[cfg(unix)]
+    Socket {
+        path: PathBuf,
+        clients: Arc<Mutex<Vec<UnixStream>>>,
+    },
+}
+
+/// A stream of one JSON object per line, for editor integrations and other tools that
+/// shouldn't have to scrape human-readable output
+pub(crate) struct Events {
+    target: Option<Target>,
+}
+
+impl Events {
+    pub(crate) fn disabled() -> Self {
+        Events { target: None }
+    }
+
+    pub(crate) fn stdout() -> Self {
+        Events {
+            target: Some(Target::Stdout),
+        }
+    }
+
+    /// Listens on a Unix domain socket at `path`, replacing any stale socket file
+    ///
+    /// Events are written from the thread that watches for changes, so a client that
+    /// stops reading gets a short write timeout and is dropped, rather than stalling it
+    #[cfg(unix)]
+    pub(crate) fn socket(path: &str) -> io::Result<Self> {
+        let path = PathBuf::from(path);
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+        let clients = Arc::new(Mutex::new(Vec::new()));
+        let accepted = clients.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if stream
+                    .set_write_timeout(Some(Duration::from_millis(100)))
+                    .is_ok()
+                {
+                    accepted.lock().unwrap().push(stream);
+                }
+            }
+        });
+        Ok(Events {
+            target: Some(Target::Socket { path, clients }),
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn socket(_path: &str) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "event sockets are only supported on Unix",
+        ))
+    }
+
+    /// Whether stdout carries events, in which case human-readable output can't go there
+    pub(crate) fn uses_stdout(&self) -> bool {
+        matches!(self.target, Some(Target::Stdout))
+    }
+
+    /// Sends `{"event": <event>, "timestamp": <ms since epoch>, ...fields}`
+    pub(crate) fn emit(&self, event: &str, fields: Value) {
+        let Some(target) = &self.target else {
+            return;
+        };
+        let mut object = json!({
+            "event": event,
+            "timestamp": SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64),
+        });
+        if let (Some(object), Value::Object(fields)) = (object.as_object_mut(), fields) {
+            object.extend(fields);
+        }
+        let line = object.to_string() + "\n";
+
+        match target {
+            Target::Stdout => {
+                let mut stdout = io::stdout().lock();
+                let _ = stdout.write_all(line.as_bytes());
+                let _ = stdout.flush();
+            }
+            #[cfg(unix)]
+            Target::Socket { clients, .. } => {
+                // clients that have disconnected, or timed out, are dropped
+                clients
+                    .lock()
+                    .unwrap()
+                    .retain_mut(|client| client.write_all(line.as_bytes()).is_ok());
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Events {
+    fn drop(&mut self) {
+        if let Some(Target::Socket { path, .. }) = &self.target {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
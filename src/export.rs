@@ -0,0 +1,570 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::str::FromStr;
+
+use super::cycle_detection::{detect_cycles_in_graph, edge_frequencies};
+use super::graph::collapse_graph;
+use super::ruff_util::{ruff_graph, Direction};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    #[default]
+    Dot,
+    Mermaid,
+    Graphml,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(ExportFormat::Dot),
+            "mermaid" => Ok(ExportFormat::Mermaid),
+            "graphml" => Ok(ExportFormat::Graphml),
+            _ => Err(format!(
+                "unknown export format '{}', expected 'dot', 'mermaid' or 'graphml'",
+                s
+            )),
+        }
+    }
+}
+
+/// Whether the graph's nodes are dotted module names or file paths
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Module,
+    File,
+}
+
+impl NodeKind {
+    fn separator(&self) -> char {
+        match self {
+            NodeKind::Module => '.',
+            NodeKind::File => '/',
+        }
+    }
+}
+
+impl FromStr for NodeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "module" => Ok(NodeKind::Module),
+            "file" => Ok(NodeKind::File),
+            _ => Err(format!(
+                "unknown node kind '{}', expected 'module' or 'file'",
+                s
+            )),
+        }
+    }
+}
+
+/// The import graph as it gets rendered, after filtering and collapsing modules
+#[derive(Debug, PartialEq)]
+pub(crate) struct ExportGraph {
+    nodes: BTreeSet<String>,
+    /// each import, mapped to the number of cycles it appears in
+    edges: BTreeMap<(String, String), u32>,
+    separator: char,
+    clusters: bool,
+}
+
+impl ExportGraph {
+    pub(crate) fn new(
+        graph: &HashMap<String, HashSet<String>>,
+        options: &super::ExportOptions,
+    ) -> Self {
+        let separator = options.nodes.separator();
+        let subtree = options
+            .subtree
+            .as_deref()
+            .map(|subtree| subtree.trim_end_matches(separator));
+        let in_subtree = |module: &str| match subtree {
+            Some(subtree) => {
+                module == subtree
+                    || module
+                        .strip_prefix(subtree)
+                        .is_some_and(|rest| rest.starts_with(separator))
+            }
+            None => true,
+        };
+        let subgraph = graph
+            .iter()
+            .filter(|(module, _)| in_subtree(module))
+            .map(|(module, dependencies)| {
+                (
+                    module.clone(),
+                    dependencies
+                        .iter()
+                        .filter(|d| in_subtree(d))
+                        .cloned()
+                        .collect::<HashSet<_>>(),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        let collapsed = collapse_graph(&subgraph, options.depth, separator);
+
+        let cycles = detect_cycles_in_graph(&collapsed);
+        let frequencies = edge_frequencies(&cycles);
+        let in_cycle = cycles.iter().flatten().copied().collect::<HashSet<_>>();
+
+        let mut nodes = BTreeSet::new();
+        let mut edges = BTreeMap::new();
+        for (module, dependencies) in collapsed.iter() {
+            if options.cycles_only && !in_cycle.contains(module.as_str()) {
+                continue;
+            }
+            nodes.insert(module.clone());
+            for dependency in dependencies.iter() {
+                if options.cycles_only && !in_cycle.contains(dependency.as_str()) {
+                    continue;
+                }
+                nodes.insert(dependency.clone());
+                let frequency = frequencies
+                    .get(&(module.as_str(), dependency.as_str()))
+                    .copied()
+                    .unwrap_or(0);
+                edges.insert((module.clone(), dependency.clone()), frequency);
+            }
+        }
+        ExportGraph {
+            nodes,
+            edges,
+            separator,
+            clusters: options.clusters,
+        }
+    }
+
+    /// Nodes grouped by the package they're in, or all under `None` without `--clusters`
+    fn clusters(&self) -> BTreeMap<Option<&str>, Vec<&str>> {
+        let mut clusters: BTreeMap<Option<&str>, Vec<&str>> = BTreeMap::new();
+        for node in self.nodes.iter() {
+            let package = match self.clusters {
+                true => node.rsplit_once(self.separator).map(|(package, _)| package),
+                false => None,
+            };
+            clusters.entry(package).or_default().push(node);
+        }
+        clusters
+    }
+
+    fn max_frequency(&self) -> u32 {
+        self.edges.values().copied().max().unwrap_or(0)
+    }
+
+    /// Thicker the more cycles an import is part of
+    fn width(&self, frequency: u32) -> f64 {
+        1.0 + 4.0 * frequency as f64 / self.max_frequency() as f64
+    }
+
+    /// Renders Graphviz DOT, with imports that are part of cycles in red, drawn thicker
+    /// and weighted more heavily the more cycles they're part of
+    pub(crate) fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph imports {\n    rankdir=LR;\n    node [shape=box];\n");
+        for (package, nodes) in self.clusters() {
+            let indent = match package {
+                Some(package) => {
+                    dot.push_str(&format!(
+                        "    subgraph {} {{\n        label={};\n",
+                        dot_id(&format!("cluster_{}", package)),
+                        dot_id(package)
+                    ));
+                    "        "
+                }
+                None => "    ",
+            };
+            for node in nodes {
+                dot.push_str(&format!("{}{};\n", indent, dot_id(node)));
+            }
+            if package.is_some() {
+                dot.push_str("    }\n");
+            }
+        }
+        for ((from, to), frequency) in self.edges.iter() {
+            dot.push_str(&format!("    {} -> {}", dot_id(from), dot_id(to)));
+            if *frequency > 0 {
+                dot.push_str(&format!(
+                    " [color=red, penwidth={:.1}, weight={}, tooltip=\"in {} cycle(s)\"]",
+                    self.width(*frequency),
+                    frequency,
+                    frequency
+                ));
+            }
+            dot.push_str(";\n");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Renders a Mermaid flowchart, which GitHub renders in Markdown; node ids are
+    /// generated since module names and paths aren't valid Mermaid ids
+    pub(crate) fn to_mermaid(&self) -> String {
+        let ids = self.ids();
+        let mut mermaid = String::from("flowchart LR\n");
+        for (i, (package, nodes)) in self.clusters().into_iter().enumerate() {
+            let indent = match package {
+                Some(package) => {
+                    mermaid.push_str(&format!(
+                        "    subgraph c{}[{}]\n",
+                        i,
+                        mermaid_label(package)
+                    ));
+                    "        "
+                }
+                None => "    ",
+            };
+            for node in nodes {
+                mermaid.push_str(&format!(
+                    "{}{}[{}]\n",
+                    indent,
+                    ids[node],
+                    mermaid_label(node)
+                ));
+            }
+            if package.is_some() {
+                mermaid.push_str("    end\n");
+            }
+        }
+        for (from, to) in self.edges.keys() {
+            mermaid.push_str(&format!(
+                "    {} --> {}\n",
+                ids[from.as_str()],
+                ids[to.as_str()]
+            ));
+        }
+        // links can only be styled by their index, in the order they were declared
+        for (i, frequency) in self.edges.values().enumerate() {
+            if *frequency > 0 {
+                mermaid.push_str(&format!(
+                    "    linkStyle {} stroke:red,stroke-width:{:.1}px\n",
+                    i,
+                    self.width(*frequency)
+                ));
+            }
+        }
+        mermaid
+    }
+
+    /// Renders GraphML for yEd, Gephi and the like, with packages as nested graphs and
+    /// the number of cycles each import is part of as an edge attribute
+    pub(crate) fn to_graphml(&self) -> String {
+        let ids = self.ids();
+        let mut graphml = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n",
+            "  <key id=\"cycles\" for=\"edge\" attr.name=\"cycles\" attr.type=\"int\">\n",
+            "    <default>0</default>\n",
+            "  </key>\n",
+            "  <graph id=\"imports\" edgedefault=\"directed\">\n",
+        ));
+        for (i, (package, nodes)) in self.clusters().into_iter().enumerate() {
+            let indent = match package {
+                Some(package) => {
+                    graphml.push_str(&format!(
+                        concat!(
+                            "    <node id=\"c{}\">\n",
+                            "      <data key=\"label\">{}</data>\n",
+                            "      <graph id=\"c{}:\" edgedefault=\"directed\">\n"
+                        ),
+                        i,
+                        xml_escape(package),
+                        i
+                    ));
+                    "        "
+                }
+                None => "    ",
+            };
+            for node in nodes {
+                graphml.push_str(&format!(
+                    "{}<node id=\"{}\"><data key=\"label\">{}</data></node>\n",
+                    indent,
+                    ids[node],
+                    xml_escape(node)
+                ));
+            }
+            if package.is_some() {
+                graphml.push_str("      </graph>\n    </node>\n");
+            }
+        }
+        for ((from, to), frequency) in self.edges.iter() {
+            graphml.push_str(&format!(
+                "    <edge source=\"{}\" target=\"{}\"",
+                ids[from.as_str()],
+                ids[to.as_str()]
+            ));
+            match frequency {
+                0 => graphml.push_str("/>\n"),
+                _ => graphml.push_str(&format!(
+                    "><data key=\"cycles\">{}</data></edge>\n",
+                    frequency
+                )),
+            }
+        }
+        graphml.push_str("  </graph>\n</graphml>\n");
+        graphml
+    }
+
+    fn ids(&self) -> HashMap<&str, String> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (node.as_str(), format!("n{}", i)))
+            .collect()
+    }
+}
+
+fn dot_id(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn mermaid_label(label: &str) -> String {
+    format!("\"{}\"", label.replace('"', "#quot;"))
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub(crate) fn export(options: super::ExportOptions) -> Result<(), Box<dyn Error>> {
+    let graph = ruff_graph(
+        options.nodes == NodeKind::Module,
+        Direction::Dependencies,
+        None,
+    );
+    let export_graph = ExportGraph::new(&graph, &options);
+    let rendered = match options.format.unwrap_or_default() {
+        ExportFormat::Dot => export_graph.to_dot(),
+        ExportFormat::Mermaid => export_graph.to_mermaid(),
+        ExportFormat::Graphml => export_graph.to_graphml(),
+    };
+    match &options.output {
+        Some(output) => {
+            fs::write(output, rendered).map_err(|e| format!("couldn't write {}: {}", output, e))?
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    /// two cycles through foo.a -> bar.c, and baz outside of any
+    fn graph() -> HashMap<String, HashSet<String>> {
+        HashMap::from([
+            ("foo.a".to_string(), HashSet::from(["bar.c".to_string()])),
+            ("foo.b".to_string(), HashSet::from(["foo.a".to_string()])),
+            (
+                "bar.c".to_string(),
+                HashSet::from(["bar.d.e".to_string(), "foo.b".to_string()]),
+            ),
+            ("bar.d.e".to_string(), HashSet::from(["foo.a".to_string()])),
+            ("baz".to_string(), HashSet::from(["foo.a".to_string()])),
+        ])
+    }
+
+    fn build(args: &[&str]) -> ExportGraph {
+        let options = super::super::ExportOptions::from_iter([&["export"], args].concat());
+        ExportGraph::new(&graph(), &options)
+    }
+
+    fn edges(export_graph: &ExportGraph) -> Vec<(&str, &str, u32)> {
+        export_graph
+            .edges
+            .iter()
+            .map(|((from, to), frequency)| (from.as_str(), to.as_str(), *frequency))
+            .collect()
+    }
+
+    #[test]
+    fn test_export_graph() {
+        let export_graph = build(&[]);
+        assert_eq!(export_graph.nodes.len(), 5);
+        assert_eq!(
+            edges(&export_graph),
+            [
+                ("bar.c", "bar.d.e", 1),
+                ("bar.c", "foo.b", 1),
+                ("bar.d.e", "foo.a", 1),
+                ("baz", "foo.a", 0),
+                ("foo.a", "bar.c", 2),
+                ("foo.b", "foo.a", 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_graph_subtree() {
+        let export_graph = build(&["--subtree", "bar"]);
+        assert_eq!(
+            export_graph.nodes,
+            BTreeSet::from(["bar.c".to_string(), "bar.d.e".to_string()])
+        );
+        assert_eq!(edges(&export_graph), [("bar.c", "bar.d.e", 0)]);
+        // a prefix of a module name isn't a package
+        assert!(build(&["--subtree", "ba"]).nodes.is_empty());
+    }
+
+    #[test]
+    fn test_export_graph_depth() {
+        let export_graph = build(&["--depth", "1"]);
+        assert_eq!(
+            export_graph.nodes,
+            BTreeSet::from(["bar".to_string(), "baz".to_string(), "foo".to_string()])
+        );
+        assert_eq!(
+            edges(&export_graph),
+            [("bar", "foo", 1), ("baz", "foo", 0), ("foo", "bar", 1)]
+        );
+    }
+
+    #[test]
+    fn test_export_graph_cycles_only() {
+        let export_graph = build(&["--cycles-only"]);
+        assert!(!export_graph.nodes.contains("baz"));
+        assert_eq!(export_graph.nodes.len(), 4);
+        assert_eq!(export_graph.edges.len(), 5);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let export_graph = build(&["--depth", "1"]);
+        assert_eq!(
+            export_graph.to_dot(),
+            r#"digraph imports {
+    rankdir=LR;
+    node [shape=box];
+    "bar";
+    "baz";
+    "foo";
+    "bar" -> "foo" [color=red, penwidth=5.0, weight=1, tooltip="in 1 cycle(s)"];
+    "baz" -> "foo";
+    "foo" -> "bar" [color=red, penwidth=5.0, weight=1, tooltip="in 1 cycle(s)"];
+}
+"#
+        );
+        assert_eq!(dot_id("a\"b"), "\"a\\\"b\"");
+    }
+
+    #[test]
+    fn test_to_dot_clusters() {
+        let export_graph = build(&["--subtree", "bar", "--clusters"]);
+        assert_eq!(
+            export_graph.to_dot(),
+            r#"digraph imports {
+    rankdir=LR;
+    node [shape=box];
+    subgraph "cluster_bar" {
+        label="bar";
+        "bar.c";
+    }
+    subgraph "cluster_bar.d" {
+        label="bar.d";
+        "bar.d.e";
+    }
+    "bar.c" -> "bar.d.e";
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_to_mermaid() {
+        assert_eq!(
+            build(&["--depth", "1"]).to_mermaid(),
+            r#"flowchart LR
+    n0["bar"]
+    n1["baz"]
+    n2["foo"]
+    n0 --> n2
+    n1 --> n2
+    n2 --> n0
+    linkStyle 0 stroke:red,stroke-width:5.0px
+    linkStyle 2 stroke:red,stroke-width:5.0px
+"#
+        );
+        assert_eq!(
+            build(&["--subtree", "bar", "--clusters"]).to_mermaid(),
+            r#"flowchart LR
+    subgraph c0["bar"]
+        n0["bar.c"]
+    end
+    subgraph c1["bar.d"]
+        n1["bar.d.e"]
+    end
+    n0 --> n1
+"#
+        );
+    }
+
+    #[test]
+    fn test_to_graphml() {
+        assert_eq!(
+            build(&["--subtree", "foo", "--clusters"]).to_graphml(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="label" for="node" attr.name="label" attr.type="string"/>
+  <key id="cycles" for="edge" attr.name="cycles" attr.type="int">
+    <default>0</default>
+  </key>
+  <graph id="imports" edgedefault="directed">
+    <node id="c0">
+      <data key="label">foo</data>
+      <graph id="c0:" edgedefault="directed">
+        <node id="n0"><data key="label">foo.a</data></node>
+        <node id="n1"><data key="label">foo.b</data></node>
+      </graph>
+    </node>
+    <edge source="n1" target="n0"/>
+  </graph>
+</graphml>
+"#
+        );
+        assert_eq!(xml_escape("<a & \"b\">"), "&lt;a &amp; &quot;b&quot;&gt;");
+    }
+
+    #[test]
+    fn test_export_graph_files() {
+        let graph = HashMap::from([
+            (
+                "src/foo/a.py".to_string(),
+                HashSet::from(["src/bar/c.py".to_string()]),
+            ),
+            (
+                "src/bar/c.py".to_string(),
+                HashSet::from(["src/foo/a.py".to_string()]),
+            ),
+            (
+                "src/baz.py".to_string(),
+                HashSet::from(["src/foo/a.py".to_string()]),
+            ),
+        ]);
+        let options = super::super::ExportOptions::from_iter([
+            "export",
+            "--nodes",
+            "file",
+            "--subtree",
+            "src/",
+            "--depth",
+            "2",
+        ]);
+        let export_graph = ExportGraph::new(&graph, &options);
+        assert_eq!(
+            edges(&export_graph),
+            [
+                ("src/bar", "src/foo", 1),
+                ("src/baz.py", "src/foo", 0),
+                ("src/foo", "src/bar", 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::{HashMap, HashSet};
+
+/// Collapses a module into its package `depth` levels deep, e.g. `foo.bar.baz` at depth 2
+/// is `foo.bar`; `separator` is `.` for modules and `/` for file paths
+pub(crate) fn collapse(module: &str, depth: Option<usize>, separator: char) -> String {
+    match depth {
+        Some(depth) => module
+            .split(separator)
+            .take(depth)
+            .collect::<Vec<_>>()
+            .join(&separator.to_string()),
+        None => module.to_string(),
+    }
+}
+
+/// The graph with every module collapsed into its package, without the self-imports
+/// that collapsing creates
+pub(crate) fn collapse_graph(
+    graph: &HashMap<String, HashSet<String>>,
+    depth: Option<usize>,
+    separator: char,
+) -> HashMap<String, HashSet<String>> {
+    let mut collapsed: HashMap<String, HashSet<String>> = HashMap::new();
+    for (module, dependencies) in graph.iter() {
+        let module = collapse(module, depth, separator);
+        let collapsed_dependencies = dependencies
+            .iter()
+            .map(|d| collapse(d, depth, separator))
+            .filter(|d| *d != module)
+            .collect::<HashSet<_>>();
+        collapsed
+            .entry(module)
+            .or_default()
+            .extend(collapsed_dependencies);
+    }
+    collapsed
+}
+
+/// Flips the direction of every edge, e.g. from dependencies to dependents; every node
+/// gets an entry, even if nothing points to it
+pub(crate) fn reverse(
+    graph: &HashMap<String, HashSet<String>>,
+) -> HashMap<String, HashSet<String>> {
+    let mut reversed: HashMap<String, HashSet<String>> = HashMap::new();
+    for (node, targets) in graph.iter() {
+        reversed.entry(node.clone()).or_default();
+        for target in targets.iter() {
+            reversed
+                .entry(target.clone())
+                .or_default()
+                .insert(node.clone());
+        }
+    }
+    reversed
+}
+
+/// Tarjan's algorithm, iteratively since import chains can be deep enough to overflow
+/// the stack; components come out in reverse topological order, i.e. a component only
+/// points to components before it
+pub(crate) fn strongly_connected_components(
+    graph: &HashMap<String, HashSet<String>>,
+) -> Vec<Vec<&str>> {
+    let mut nodes = graph
+        .keys()
+        .chain(graph.values().flatten())
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    nodes.sort();
+    nodes.dedup();
+    let successors = |node: &str| {
+        let mut successors = graph
+            .get(node)
+            .map(|s| s.iter().map(String::as_str).collect::<Vec<_>>())
+            .unwrap_or_default();
+        successors.sort();
+        successors
+    };
+
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    let mut low_link: HashMap<&str, usize> = HashMap::new();
+    let mut on_stack: HashSet<&str> = HashSet::new();
+    let mut stack: Vec<&str> = Vec::new();
+    let mut components = Vec::new();
+
+    for root in nodes {
+        if index.contains_key(root) {
+            continue;
+        }
+        // each frame is a node and the successors it still has to visit
+        let mut frames = vec![(root, successors(root), 0)];
+        index.insert(root, index.len());
+        low_link.insert(root, index[root]);
+        stack.push(root);
+        on_stack.insert(root);
+
+        while let Some((node, node_successors, next)) = frames.last_mut() {
+            let node = *node;
+            if let Some(&successor) = node_successors.get(*next) {
+                *next += 1;
+                if !index.contains_key(successor) {
+                    index.insert(successor, index.len());
+                    low_link.insert(successor, index[successor]);
+                    stack.push(successor);
+                    on_stack.insert(successor);
+                    frames.push((successor, successors(successor), 0));
+                } else if on_stack.contains(successor) {
+                    low_link.insert(node, low_link[node].min(index[successor]));
+                }
+                continue;
+            }
+
+            frames.pop();
+            if let Some((parent, _, _)) = frames.last() {
+                low_link.insert(parent, low_link[parent].min(low_link[node]));
+            }
+            if low_link[node] == index[node] {
+                let mut component = Vec::new();
+                while let Some(member) = stack.pop() {
+                    on_stack.remove(member);
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                component.sort();
+                components.push(component);
+            }
+        }
+    }
+    components
+}
+
+/// Everything that depends on each node, directly or indirectly, including the node
+/// itself: what `live::get_affected_files` finds, but for every node at once
+///
+/// Nodes in a strongly-connected component share the same dependents, so this is
+/// computed once per component over the condensed graph, as bitsets over the nodes.
+pub(crate) struct TransitiveDependents<'a> {
+    nodes: Vec<&'a str>,
+    component: HashMap<&'a str, usize>,
+    dependents: Vec<Vec<u64>>,
+}
+
+impl<'a> TransitiveDependents<'a> {
+    /// `graph` maps each node to its dependencies
+    pub(crate) fn new(graph: &'a HashMap<String, HashSet<String>>) -> Self {
+        let components = strongly_connected_components(graph);
+        let nodes = components.iter().flatten().copied().collect::<Vec<_>>();
+        let index = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (*node, i))
+            .collect::<HashMap<_, _>>();
+        let component = components
+            .iter()
+            .enumerate()
+            .flat_map(|(c, members)| members.iter().map(move |node| (*node, c)))
+            .collect::<HashMap<_, _>>();
+
+        let words = nodes.len().div_ceil(64);
+        let mut dependents = vec![vec![0u64; words]; components.len()];
+        // components come out dependencies first, so going backwards every importer's
+        // dependents are complete before they're added to what it imports
+        for (c, members) in components.iter().enumerate().rev() {
+            for node in members.iter() {
+                dependents[c][index[node] / 64] |= 1 << (index[node] % 64);
+            }
+            let dependencies = members
+                .iter()
+                .flat_map(|node| graph.get(*node).into_iter().flatten())
+                .map(|dependency| component[dependency.as_str()])
+                .filter(|d| *d != c)
+                .collect::<HashSet<_>>();
+            // the components `c` imports get its dependents too
+            for d in dependencies {
+                let (before, after) = dependents.split_at_mut(c);
+                for (word, from) in before[d].iter_mut().zip(after[0].iter()) {
+                    *word |= from;
+                }
+            }
+        }
+        TransitiveDependents {
+            nodes,
+            component,
+            dependents,
+        }
+    }
+
+    /// Transitive dependents of `node`, including itself; nothing for unknown nodes
+    pub(crate) fn get(&self, node: &str) -> impl Iterator<Item = &'a str> + '_ {
+        let bits = self.component.get(node).map(|c| &self.dependents[*c]);
+        bits.into_iter().flat_map(move |bits| {
+            bits.iter().enumerate().flat_map(move |(w, word)| {
+                (0..64)
+                    .filter(move |b| word & (1 << b) != 0)
+                    .map(move |b| self.nodes[w * 64 + b])
+            })
+        })
+    }
+
+    pub(crate) fn count(&self, node: &str) -> usize {
+        self.component.get(node).map_or(0, |c| {
+            self.dependents[*c]
+                .iter()
+                .map(|word| word.count_ones() as usize)
+                .sum()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(&str, &[&str])]) -> HashMap<String, HashSet<String>> {
+        edges
+            .iter()
+            .map(|(node, targets)| {
+                (
+                    node.to_string(),
+                    targets.iter().map(|t| t.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_strongly_connected_components() {
+        let graph = graph(&[
+            ("a", &["b"]),
+            ("b", &["c", "d"]),
+            ("c", &["a"]),
+            ("d", &["e"]),
+            ("e", &["d", "f"]),
+        ]);
+        assert_eq!(
+            strongly_connected_components(&graph),
+            vec![vec!["f"], vec!["d", "e"], vec!["a", "b", "c"]]
+        );
+    }
+
+    #[test]
+    fn test_transitive_dependents() {
+        let graph = graph(&[
+            ("a", &["b"]),
+            ("b", &["c", "d"]),
+            ("c", &["a"]),
+            ("d", &["e"]),
+            ("e", &["d", "f"]),
+            ("g", &["f"]),
+        ]);
+        let dependents = TransitiveDependents::new(&graph);
+        let get = |node| {
+            let mut dependents = dependents.get(node).collect::<Vec<_>>();
+            dependents.sort();
+            dependents
+        };
+        assert_eq!(get("f"), ["a", "b", "c", "d", "e", "f", "g"]);
+        assert_eq!(get("d"), ["a", "b", "c", "d", "e"]);
+        assert_eq!(get("a"), ["a", "b", "c"]);
+        assert_eq!(get("g"), ["g"]);
+        assert_eq!(get("unknown"), Vec::<&str>::new());
+        assert_eq!(dependents.count("d"), 5);
+
+        // agrees with a BFS from every node, also across word boundaries
+        let chain = (0..100)
+            .map(|i| (i.to_string(), HashSet::from([(i + 1).to_string()])))
+            .collect::<HashMap<_, _>>();
+        let dependents = TransitiveDependents::new(&chain);
+        let reversed = reverse(&chain);
+        for node in reversed.keys() {
+            let expected = crate::live::get_affected_files(std::slice::from_ref(node), &reversed);
+            assert_eq!(
+                dependents
+                    .get(node)
+                    .map(str::to_string)
+                    .collect::<HashSet<_>>(),
+                expected
+            );
+            assert_eq!(dependents.count(node), expected.len());
+        }
+    }
+
+    #[test]
+    fn test_collapse_graph() {
+        let graph = graph(&[
+            ("foo.a", &["foo.b", "bar.c"]),
+            ("foo.b", &[]),
+            ("bar.c", &["foo.a"]),
+        ]);
+        assert_eq!(
+            collapse_graph(&graph, Some(1), '.'),
+            HashMap::from([
+                ("foo".to_string(), HashSet::from(["bar".to_string()])),
+                ("bar".to_string(), HashSet::from(["foo".to_string()])),
+            ])
+        );
+        assert_eq!(collapse("src/foo/a.py", Some(2), '/'), "src/foo");
+        assert_eq!(collapse("foo.a", None, '.'), "foo.a");
+    }
+
+    #[test]
+    fn test_reverse() {
+        let graph = graph(&[("a", &["b", "c"]), ("b", &["c"])]);
+        assert_eq!(
+            reverse(&graph),
+            HashMap::from([
+                ("a".to_string(), HashSet::new()),
+                ("b".to_string(), HashSet::from(["a".to_string()])),
+                (
+                    "c".to_string(),
+                    HashSet::from(["a".to_string(), "b".to_string()])
+                ),
+            ])
+        );
+    }
+}
@@ -0,0 +1,301 @@
+use serde_json::json;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::cycle_detection::detect_cycles_in_graph;
+use super::ruff_util::{ruff_graph_in, Direction};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DiffFormat {
+    #[default]
+    Markdown,
+    Json,
+}
+
+impl FromStr for DiffFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" => Ok(DiffFormat::Markdown),
+            "json" => Ok(DiffFormat::Json),
+            _ => Err(format!(
+                "unknown diff format '{}', expected 'markdown' or 'json'",
+                s
+            )),
+        }
+    }
+}
+
+/// A checkout of `rev` in a temporary directory, removed again when dropped
+struct Worktree {
+    path: PathBuf,
+}
+
+/// Numbers the worktrees of this process, since sanitized revs can collide, e.g.
+/// `feature/x` and `feature-x`
+static WORKTREES: AtomicUsize = AtomicUsize::new(0);
+
+impl Worktree {
+    fn add(rev: &str) -> Result<Self, Box<dyn Error>> {
+        let path = std::env::temp_dir().join(format!(
+            "ruff-tools-graph-diff-{}-{}-{}",
+            std::process::id(),
+            WORKTREES.fetch_add(1, Ordering::SeqCst),
+            rev.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        ));
+        git([
+            OsStr::new("worktree"),
+            OsStr::new("add"),
+            OsStr::new("--detach"),
+            OsStr::new("--force"),
+            path.as_os_str(),
+            OsStr::new(rev),
+        ])?;
+        Ok(Worktree { path })
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        let _ = Command::new("git")
+            .args(["worktree", "remove", "--force"])
+            .arg(&self.path)
+            .output();
+    }
+}
+
+fn git<S: AsRef<OsStr>>(args: impl IntoIterator<Item = S>) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("git").args(args).output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "git failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
+/// How the graph changed between two revisions; contract status changes belong here too,
+/// once `lint-imports` has contracts to check
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct GraphDiff {
+    added_modules: BTreeSet<String>,
+    removed_modules: BTreeSet<String>,
+    added_imports: BTreeSet<(String, String)>,
+    removed_imports: BTreeSet<(String, String)>,
+    new_cycles: BTreeSet<Vec<String>>,
+    resolved_cycles: BTreeSet<Vec<String>>,
+}
+
+impl GraphDiff {
+    pub(crate) fn new(
+        before: &HashMap<String, HashSet<String>>,
+        after: &HashMap<String, HashSet<String>>,
+    ) -> Self {
+        let modules = |graph: &HashMap<String, HashSet<String>>| {
+            graph
+                .keys()
+                .chain(graph.values().flatten())
+                .cloned()
+                .collect::<BTreeSet<_>>()
+        };
+        let imports = |graph: &HashMap<String, HashSet<String>>| {
+            graph
+                .iter()
+                .flat_map(|(module, dependencies)| {
+                    dependencies.iter().map(|d| (module.clone(), d.clone()))
+                })
+                .collect::<BTreeSet<_>>()
+        };
+        let cycles = |graph: &HashMap<String, HashSet<String>>| {
+            detect_cycles_in_graph(graph)
+                .into_iter()
+                .map(|cycle| cycle.into_iter().map(str::to_string).collect::<Vec<_>>())
+                .collect::<BTreeSet<_>>()
+        };
+
+        let (modules_before, modules_after) = (modules(before), modules(after));
+        let (imports_before, imports_after) = (imports(before), imports(after));
+        let (cycles_before, cycles_after) = (cycles(before), cycles(after));
+        GraphDiff {
+            added_modules: &modules_after - &modules_before,
+            removed_modules: &modules_before - &modules_after,
+            added_imports: &imports_after - &imports_before,
+            removed_imports: &imports_before - &imports_after,
+            new_cycles: &cycles_after - &cycles_before,
+            resolved_cycles: &cycles_before - &cycles_after,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == GraphDiff::default()
+    }
+
+    /// Renders Markdown meant to be posted as a PR comment
+    pub(crate) fn to_markdown(&self, rev_a: &str, rev_b: &str) -> String {
+        let mut markdown = format!("## Import graph changes `{}`...`{}`\n\n", rev_a, rev_b);
+        if self.is_empty() {
+            markdown.push_str("No changes to the import graph.\n");
+            return markdown;
+        }
+        markdown.push_str(&format!(
+            "| | added | removed |\n| --- | --- | --- |\n| modules | {} | {} |\n| imports | {} | {} |\n| cycles | {} | {} |\n",
+            self.added_modules.len(),
+            self.removed_modules.len(),
+            self.added_imports.len(),
+            self.removed_imports.len(),
+            self.new_cycles.len(),
+            self.resolved_cycles.len(),
+        ));
+
+        let mut section = |title: &str, items: Vec<String>| {
+            if !items.is_empty() {
+                markdown.push_str(&format!("\n### {}\n", title));
+                for item in items {
+                    markdown.push_str(&format!("- `{}`\n", item));
+                }
+            }
+        };
+        let cycles = |cycles: &BTreeSet<Vec<String>>| {
+            cycles.iter().map(|c| c.join(" -> ")).collect::<Vec<_>>()
+        };
+        let imports = |imports: &BTreeSet<(String, String)>| {
+            imports
+                .iter()
+                .map(|(from, to)| format!("{} -> {}", from, to))
+                .collect::<Vec<_>>()
+        };
+        section("New cycles", cycles(&self.new_cycles));
+        section("Resolved cycles", cycles(&self.resolved_cycles));
+        section(
+            "Added modules",
+            self.added_modules.iter().cloned().collect(),
+        );
+        section(
+            "Removed modules",
+            self.removed_modules.iter().cloned().collect(),
+        );
+        section("Added imports", imports(&self.added_imports));
+        section("Removed imports", imports(&self.removed_imports));
+        markdown
+    }
+
+    pub(crate) fn to_json(&self) -> String {
+        let imports = |imports: &BTreeSet<(String, String)>| {
+            imports
+                .iter()
+                .map(|(from, to)| json!({"from": from, "to": to}))
+                .collect::<Vec<_>>()
+        };
+        let diff = json!({
+            "added_modules": self.added_modules,
+            "removed_modules": self.removed_modules,
+            "added_imports": imports(&self.added_imports),
+            "removed_imports": imports(&self.removed_imports),
+            "new_cycles": self.new_cycles,
+            "resolved_cycles": self.resolved_cycles,
+        });
+        serde_json::to_string_pretty(&diff).unwrap() + "\n"
+    }
+}
+
+/// The graph at `rev`, for the same directory within the repository as the current one
+fn graph_at(rev: &str, prefix: &str) -> Result<HashMap<String, HashSet<String>>, Box<dyn Error>> {
+    let worktree = Worktree::add(rev)?;
+    Ok(ruff_graph_in(
+        &worktree.path.join(prefix),
+        true,
+        Direction::Dependencies,
+        None,
+    ))
+}
+
+pub(crate) fn graph_diff(options: super::GraphDiffOptions) -> Result<(), Box<dyn Error>> {
+    let prefix = git(["rev-parse", "--show-prefix"])?;
+    let before = graph_at(&options.rev_a, &prefix)?;
+    let after = graph_at(&options.rev_b, &prefix)?;
+    let diff = GraphDiff::new(&before, &after);
+    match options.format.unwrap_or_default() {
+        DiffFormat::Markdown => print!("{}", diff.to_markdown(&options.rev_a, &options.rev_b)),
+        DiffFormat::Json => print!("{}", diff.to_json()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(&str, &[&str])]) -> HashMap<String, HashSet<String>> {
+        edges
+            .iter()
+            .map(|(module, dependencies)| {
+                (
+                    module.to_string(),
+                    dependencies.iter().map(|d| d.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_graph_diff() {
+        let before = graph(&[("a", &["b"]), ("b", &["c"]), ("c", &[])]);
+        let after = graph(&[("a", &["b"]), ("b", &["a"]), ("d", &["a"])]);
+        let diff = GraphDiff::new(&before, &after);
+
+        assert_eq!(diff.added_modules, BTreeSet::from(["d".to_string()]));
+        assert_eq!(diff.removed_modules, BTreeSet::from(["c".to_string()]));
+        assert_eq!(
+            diff.added_imports,
+            BTreeSet::from([
+                ("b".to_string(), "a".to_string()),
+                ("d".to_string(), "a".to_string()),
+            ])
+        );
+        assert_eq!(
+            diff.removed_imports,
+            BTreeSet::from([("b".to_string(), "c".to_string())])
+        );
+        assert_eq!(
+            diff.new_cycles,
+            BTreeSet::from([vec!["a".to_string(), "b".to_string()]])
+        );
+        assert!(diff.resolved_cycles.is_empty());
+        assert!(GraphDiff::new(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        let before = graph(&[("a", &["b"]), ("b", &[])]);
+        let after = graph(&[("a", &["b"]), ("b", &["a"])]);
+        assert_eq!(
+            GraphDiff::new(&before, &after).to_markdown("main", "HEAD"),
+            "\
+## Import graph changes `main`...`HEAD`
+
+| | added | removed |
+| --- | --- | --- |
+| modules | 0 | 0 |
+| imports | 1 | 0 |
+| cycles | 1 | 0 |
+
+### New cycles
+- `a -> b`
+
+### Added imports
+- `b -> a`
+"
+        );
+        assert!(GraphDiff::new(&before, &before)
+            .to_markdown("main", "HEAD")
+            .ends_with("No changes to the import graph.\n"));
+    }
+}
@@ -0,0 +1,532 @@
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::sync::LazyLock;
+
+use super::graph::strongly_connected_components;
+use super::ruff_util::path_to_module;
+
+static IMPORT_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^import\s+(.+)$").unwrap());
+static FROM_IMPORT_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^from\s+(\.*)\s*([\w.]*)\s+import\s+(.+)$").unwrap());
+static TYPE_CHECKING_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^if\s+(?:typing\.)?TYPE_CHECKING\s*:(.*)$").unwrap());
+static SUPPRESSION_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"#\s*(?:ruff-tools:\s*ignore-cycle\b|noqa:.*\bcyclic-import\b)").unwrap()
+});
+static DEF_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:async\s+)?def\s.*:(.*)$").unwrap());
+
+/// When an import actually happens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum ImportKind {
+    /// only for type checkers, inside `if TYPE_CHECKING:`
+    TypeChecking,
+    /// when a function runs, rather than when the module is imported
+    Deferred,
+    /// when the module is imported
+    ModuleLevel,
+}
+
+/// An import statement, and every module it could be importing
+#[derive(Debug, PartialEq)]
+pub(crate) struct Import {
+    pub(crate) line: usize,
+    pub(crate) kind: ImportKind,
+    /// whether it's marked with `# ruff-tools: ignore-cycle` or `# noqa: cyclic-import`
+    pub(crate) suppressed: bool,
+    modules: Vec<String>,
+}
+
+/// Finds the import statements in `source`, without fully parsing it: statements
+/// continued over several lines are joined, and reported at their first line, and the
+/// contents of strings and comments are skipped
+///
+/// `module` is the dotted name of the file itself, to resolve relative imports against.
+pub(crate) fn parse_imports(source: &str, module: &str, is_package: bool) -> Vec<Import> {
+    let mut imports = Vec::new();
+    // the indentation of each block the current line is in, and what kind of imports
+    // it makes for
+    let mut blocks: Vec<(usize, ImportKind)> = Vec::new();
+    let mut statement = String::new();
+    let mut start = 0;
+    let mut indent = 0;
+    let mut depth = 0i32;
+    let mut suppressed = false;
+    let mut open_string = None;
+    for (i, line) in source.lines().enumerate() {
+        let (code, comment) = split_line(line, &mut open_string);
+        let code = code.as_str();
+        if statement.is_empty() {
+            if code.trim().is_empty() {
+                continue;
+            }
+            start = i + 1;
+            indent = code.len() - code.trim_start().len();
+        }
+        suppressed |= SUPPRESSION_REGEX.is_match(comment);
+        statement.push_str(code.trim_end_matches('\\'));
+        statement.push(' ');
+        depth += code.matches('(').count() as i32 - code.matches(')').count() as i32;
+        if depth > 0 || open_string.is_some() || code.trim_end().ends_with('\\') {
+            continue;
+        }
+        depth = 0;
+
+        while blocks.last().is_some_and(|(block, _)| *block >= indent) {
+            blocks.pop();
+        }
+        let mut kind = blocks
+            .last()
+            .map_or(ImportKind::ModuleLevel, |(_, kind)| *kind);
+        let mut body = statement.trim();
+        // a block's body may be on the same line, as in `if TYPE_CHECKING: import a`
+        for (regex, block_kind) in [
+            (&TYPE_CHECKING_REGEX, ImportKind::TypeChecking),
+            (&DEF_REGEX, ImportKind::Deferred),
+        ] {
+            if let Some(captures) = regex.captures(body) {
+                kind = kind.min(block_kind);
+                blocks.push((indent, kind));
+                body = captures.get(1).unwrap().as_str();
+                break;
+            }
+        }
+        for part in body.split(';') {
+            let modules = imported_modules(part.trim(), module, is_package);
+            if !modules.is_empty() {
+                imports.push(Import {
+                    line: start,
+                    kind,
+                    suppressed,
+                    modules,
+                });
+            }
+        }
+        statement.clear();
+        suppressed = false;
+    }
+    imports
+}
+
+/// Splits `line` into its code, with only the quotes of any strings left, and its comment;
+/// `open` is the quote of a string still open from the previous line, and is left with
+/// the one still open at the end of this one
+fn split_line<'a>(line: &'a str, open: &mut Option<&'static str>) -> (String, &'a str) {
+    let mut code = String::new();
+    let mut rest = line;
+    loop {
+        if let Some(quote) = *open {
+            let mut chars = rest.char_indices();
+            let mut end = None;
+            while let Some((i, c)) = chars.next() {
+                if c == '\\' {
+                    chars.next();
+                } else if rest[i..].starts_with(quote) {
+                    end = Some(i + quote.len());
+                    break;
+                }
+            }
+            let Some(end) = end else {
+                // only triple-quoted strings go on past the end of a line
+                if quote.len() == 1 {
+                    *open = None;
+                }
+                return (code, "");
+            };
+            code.push_str(quote);
+            rest = &rest[end..];
+            *open = None;
+        }
+        let Some(i) = rest.find(['#', '"', '\'']) else {
+            code.push_str(rest);
+            return (code, "");
+        };
+        if rest[i..].starts_with('#') {
+            code.push_str(&rest[..i]);
+            return (code, &rest[i..]);
+        }
+        let quote = ["\"\"\"", "'''", "\"", "'"]
+            .into_iter()
+            .find(|quote| rest[i..].starts_with(quote))
+            .unwrap();
+        code.push_str(&rest[..i + quote.len()]);
+        rest = &rest[i + quote.len()..];
+        *open = Some(quote);
+    }
+}
+
+fn imported_modules(statement: &str, module: &str, is_package: bool) -> Vec<String> {
+    let names = |names: &str| {
+        names
+            .replace(['(', ')'], " ")
+            .split(',')
+            .filter_map(|name| name.split_whitespace().next().map(str::to_string))
+            .collect::<Vec<_>>()
+    };
+    if let Some(captures) = IMPORT_REGEX.captures(statement) {
+        return names(&captures[1]);
+    }
+    let Some(captures) = FROM_IMPORT_REGEX.captures(statement) else {
+        return Vec::new();
+    };
+    let level = captures[1].len();
+    let base = if level == 0 {
+        captures[2].to_string()
+    } else {
+        // `.` is the package a module is in, or a package itself
+        let mut package = module.split('.').collect::<Vec<_>>();
+        let up = if is_package { level - 1 } else { level };
+        package.truncate(package.len().saturating_sub(up));
+        package.extend(captures[2].split('.').filter(|p| !p.is_empty()));
+        package.join(".")
+    };
+    // `from a import b` imports `a`, and `a.b` if that's a module
+    let mut modules = vec![base.clone()];
+    modules.extend(
+        names(&captures[3])
+            .into_iter()
+            .filter(|name| name != "*")
+            .map(|name| format!("{}.{}", base, name)),
+    );
+    modules
+}
+
+/// The strongly connected component each file is in, so that an import is part of a
+/// cycle exactly when both files are in the same one
+fn components(graph: &HashMap<String, HashSet<String>>) -> HashMap<&str, usize> {
+    strongly_connected_components(graph)
+        .into_iter()
+        .enumerate()
+        .flat_map(|(c, members)| members.into_iter().map(move |file| (file, c)))
+        .collect()
+}
+
+/// Finds where files import each other, reading and scanning each file only once
+#[derive(Default)]
+pub(crate) struct ImportLocator {
+    imports: HashMap<String, Vec<Import>>,
+}
+
+impl ImportLocator {
+    /// Lines of the import statements in `importer` that import `imported`, both paths
+    /// as `ruff` reports them; empty if they couldn't be found, e.g. for dynamic imports
+    pub(crate) fn lines(&mut self, importer: &str, imported: &str) -> Vec<usize> {
+        self.matching(importer, imported)
+            .iter()
+            .map(|import| import.line)
+            .collect()
+    }
+
+    /// When `importer` imports `imported`, going by the import that happens earliest;
+    /// imports that can't be found are assumed to happen at module level
+    pub(crate) fn kind(&mut self, importer: &str, imported: &str) -> ImportKind {
+        self.matching(importer, imported)
+            .iter()
+            .map(|import| import.kind)
+            .max()
+            .unwrap_or(ImportKind::ModuleLevel)
+    }
+
+    fn imports(&mut self, file: &str) -> &[Import] {
+        self.imports.entry(file.to_string()).or_insert_with(|| {
+            let source = fs::read_to_string(file).unwrap_or_default();
+            parse_imports(
+                &source,
+                &path_to_module(file),
+                file.ends_with("__init__.py"),
+            )
+        })
+    }
+
+    fn matching(&mut self, importer: &str, imported: &str) -> Vec<&Import> {
+        let imports = self.imports(importer);
+        let target = path_to_module(imported);
+        let exact = imports
+            .iter()
+            .filter(|import| import.modules.contains(&target))
+            .collect::<Vec<_>>();
+        if !exact.is_empty() {
+            return exact;
+        }
+        // importing a submodule imports its packages too
+        imports
+            .iter()
+            .filter(|import| {
+                import.modules.iter().any(|module| {
+                    module
+                        .strip_prefix(target.as_str())
+                        .is_some_and(|rest| rest.starts_with('.'))
+                })
+            })
+            .collect()
+    }
+
+    /// `importer:line -> imported`, with any other lines importing it, for output that
+    /// can be clicked through to the import statement
+    pub(crate) fn describe(&mut self, importer: &str, imported: &str) -> String {
+        let lines = self.lines(importer, imported);
+        match lines.split_first() {
+            None => format!("{} -> {}", importer, imported),
+            Some((first, [])) => format!("{}:{} -> {}", importer, first, imported),
+            Some((first, rest)) => format!(
+                "{}:{} -> {} (also line {})",
+                importer,
+                first,
+                imported,
+                rest.iter()
+                    .map(|l| l.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    /// `graph` of files without the imports that don't happen when a module is imported,
+    /// which can't be part of import-time cycles; only imports between files that are in
+    /// a cycle together get checked, since only those files have to be read
+    pub(crate) fn runtime_graph(
+        &mut self,
+        graph: &HashMap<String, HashSet<String>>,
+    ) -> HashMap<String, HashSet<String>> {
+        let component = components(graph);
+        graph
+            .iter()
+            .map(|(file, dependencies)| {
+                let dependencies = dependencies
+                    .iter()
+                    .filter(|dependency| {
+                        component[file.as_str()] != component[dependency.as_str()]
+                            || self.kind(file, dependency) == ImportKind::ModuleLevel
+                    })
+                    .cloned()
+                    .collect();
+                (file.clone(), dependencies)
+            })
+            .collect()
+    }
+
+    /// Removes the imports whose statements are all suppressed from `graph` of files, and
+    /// returns the suppressions that weren't needed, as `(file, line)`: those on imports
+    /// that aren't part of any cycle in the first place
+    pub(crate) fn suppress(
+        &mut self,
+        graph: &mut HashMap<String, HashSet<String>>,
+    ) -> Vec<(String, usize)> {
+        let component = components(graph);
+        let mut suppressed = Vec::new();
+        let mut unused = Vec::new();
+        for (file, dependencies) in graph.iter() {
+            let mut suppressions = self
+                .imports(file)
+                .iter()
+                .filter(|import| import.suppressed)
+                .map(|import| (import.line, false))
+                .collect::<HashMap<_, _>>();
+            if suppressions.is_empty() {
+                continue;
+            }
+            for dependency in dependencies {
+                let imports = self.matching(file, dependency);
+                if imports.is_empty() || !imports.iter().all(|import| import.suppressed) {
+                    continue;
+                }
+                suppressed.push((file.clone(), dependency.clone()));
+                if component[file.as_str()] == component[dependency.as_str()] {
+                    for import in imports {
+                        suppressions.insert(import.line, true);
+                    }
+                }
+            }
+            unused.extend(
+                suppressions
+                    .into_iter()
+                    .filter(|(_, used)| !used)
+                    .map(|(line, _)| (file.clone(), line)),
+            );
+        }
+        for (file, dependency) in suppressed {
+            graph.get_mut(&file).unwrap().remove(&dependency);
+        }
+        unused.sort();
+        unused
+    }
+
+    /// Every import in a cycle of files, described as in `describe`
+    pub(crate) fn describe_cycle(&mut self, cycle: &[&str]) -> Vec<String> {
+        (0..cycle.len())
+            .map(|i| self.describe(cycle[i], cycle[(i + 1) % cycle.len()]))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modules(source: &str) -> Vec<(usize, String)> {
+        parse_imports(source, "pkg.sub.mod", false)
+            .into_iter()
+            .map(|import| (import.line, import.modules.join(" ")))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_imports() {
+        let source = r#""""Docstring"""
+import os
+import pkg.a as a, pkg.b  # comment
+from pkg.c import d, e as f
+from . import g
+from ..other import (
+    h,
+    i,
+)
+x = 1; import pkg.j
+if TYPE_CHECKING:
+    from pkg \
+        import k
+print("import nothing")
+"#;
+        assert_eq!(
+            modules(source),
+            [
+                (2, "os".to_string()),
+                (3, "pkg.a pkg.b".to_string()),
+                (4, "pkg.c pkg.c.d pkg.c.e".to_string()),
+                (5, "pkg.sub pkg.sub.g".to_string()),
+                (6, "pkg.other pkg.other.h pkg.other.i".to_string()),
+                (10, "pkg.j".to_string()),
+                (12, "pkg pkg.k".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_imports_skips_strings() {
+        let source = r##""""Docstring
+
+import docstring
+from docstring import a
+"""
+import a  # "not a string
+x = "# not a comment"; import b
+if TYPE_CHECKING:
+    y = '''
+import c
+'''
+    import d
+    z = "#", 'import e', "\"import f"
+    s = "# noqa: cyclic-import"; import g
+"##;
+        let imports = parse_imports(source, "m", false);
+        assert_eq!(
+            imports
+                .iter()
+                .map(|import| (import.line, import.modules.join(" "), import.kind))
+                .collect::<Vec<_>>(),
+            [
+                (6, "a".to_string(), ImportKind::ModuleLevel),
+                (7, "b".to_string(), ImportKind::ModuleLevel),
+                (12, "d".to_string(), ImportKind::TypeChecking),
+                (14, "g".to_string(), ImportKind::TypeChecking),
+            ]
+        );
+        assert!(imports.iter().all(|import| !import.suppressed));
+    }
+
+    #[test]
+    fn test_parse_relative_imports_in_package() {
+        let imports = parse_imports("from . import a\nfrom .. import b\n", "pkg.sub", true);
+        assert_eq!(imports[0].modules, ["pkg.sub", "pkg.sub.a"]);
+        assert_eq!(imports[1].modules, ["pkg", "pkg.b"]);
+    }
+
+    #[test]
+    fn test_parse_import_kinds() {
+        let source = r#"import a
+if typing.TYPE_CHECKING: import b
+if TYPE_CHECKING:
+    import c
+
+    def f():
+        import d
+else:
+    import e
+
+class C:
+    import f
+
+    async def g(self,
+                x):
+        import h
+    import i
+def j(): import k
+"#;
+        let kinds = parse_imports(source, "m", false)
+            .into_iter()
+            .map(|import| (import.modules.join(" "), import.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                ("a".to_string(), ImportKind::ModuleLevel),
+                ("b".to_string(), ImportKind::TypeChecking),
+                ("c".to_string(), ImportKind::TypeChecking),
+                ("d".to_string(), ImportKind::TypeChecking),
+                ("e".to_string(), ImportKind::ModuleLevel),
+                ("f".to_string(), ImportKind::ModuleLevel),
+                ("h".to_string(), ImportKind::Deferred),
+                ("i".to_string(), ImportKind::ModuleLevel),
+                ("k".to_string(), ImportKind::Deferred),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_suppressions() {
+        let source = "import a  # ruff-tools: ignore-cycle\n\
+import b  # noqa: E402, cyclic-import\n\
+from c import (  # noqa: cyclic-import\n    d,\n)\n\
+import e  # noqa\n\
+import f  # ruff-tools: ignore-cycles\n";
+        let suppressed = parse_imports(source, "m", false)
+            .into_iter()
+            .map(|import| import.suppressed)
+            .collect::<Vec<_>>();
+        assert_eq!(suppressed, [true, true, true, false, false]);
+    }
+
+    #[test]
+    fn test_import_locator() {
+        let dir = std::env::temp_dir().join(format!("ruff-tools-imports-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/pkg")).unwrap();
+        let importer = dir.join("src/pkg/a.py");
+        fs::write(
+            &importer,
+            "import pkg.sub.c\nfrom pkg import b\n\ndef f():\n    from pkg.b import x\n",
+        )
+        .unwrap();
+        let importer = importer.to_str().unwrap();
+
+        let mut locator = ImportLocator::default();
+        assert_eq!(locator.lines(importer, "src/pkg/b.py"), [2, 5]);
+        // only imported through a submodule
+        assert_eq!(locator.lines(importer, "src/pkg/sub/__init__.py"), [1]);
+        assert!(locator.lines(importer, "src/other.py").is_empty());
+        assert_eq!(
+            locator.describe(importer, "src/pkg/b.py"),
+            format!("{}:2 -> src/pkg/b.py (also line 5)", importer)
+        );
+        assert_eq!(
+            locator.describe(importer, "src/other.py"),
+            format!("{} -> src/other.py", importer)
+        );
+        // imported at module level too
+        assert_eq!(
+            locator.kind(importer, "src/pkg/b.py"),
+            ImportKind::ModuleLevel
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+use regex::Regex;
+use std::collections::HashSet;
+
+/// Finds the files with failing or erroring tests in a JUnit XML report, such as the one
+/// written by pytest's `--junitxml`; only files for which `is_known` holds are returned
+///
+/// This isn't a full XML parser, just enough to read `<testcase>` elements. pytest's
+/// `xunit1` reports have a `file` attribute on each test case, while `xunit2` ones only
+/// have a dotted `classname`, which is matched against the known files instead.
+pub(crate) fn failed_files(report: &str, is_known: impl Fn(&str) -> bool) -> HashSet<String> {
+    let testcase_regex = Regex::new(r"(?s)<testcase\b([^>]*?)(?:/>|>(.*?)</testcase>)").unwrap();
+    let attribute_regex = Regex::new(r#"([\w-]+)="([^"]*)""#).unwrap();
+
+    testcase_regex
+        .captures_iter(report)
+        .filter(|testcase| {
+            testcase.get(2).is_some_and(|body| {
+                body.as_str().contains("<failure") || body.as_str().contains("<error")
+            })
+        })
+        .filter_map(|testcase| {
+            let attributes = attribute_regex
+                .captures_iter(&testcase[1])
+                .map(|a| (a[1].to_string(), a[2].to_string()))
+                .collect::<Vec<_>>();
+            let attribute = |name: &str| {
+                attributes
+                    .iter()
+                    .find(|(k, _)| k == name)
+                    .map(|(_, v)| v.as_str())
+            };
+            match attribute("file") {
+                Some(file) if is_known(file) => Some(file.to_string()),
+                _ => classname_to_file(attribute("classname")?, &is_known),
+            }
+        })
+        .collect()
+}
+
+/// `src.util.tests.test_foo.TestFoo` could be any prefix of its parts as a path, so try
+/// them from longest to shortest
+fn classname_to_file(classname: &str, is_known: impl Fn(&str) -> bool) -> Option<String> {
+    let parts = classname.split('.').collect::<Vec<_>>();
+    (1..=parts.len())
+        .rev()
+        .map(|i| parts[..i].join("/") + ".py")
+        .find(|candidate| is_known(candidate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failed_files() {
+        let known_files = HashSet::from([
+            "src/util/tests/test_foo.py".to_string(),
+            "src/util/tests/test_bar.py".to_string(),
+            "src/util/tests/test_baz.py".to_string(),
+            "src/util/bar.py".to_string(),
+        ]);
+        let report = r#"<?xml version="1.0" encoding="utf-8"?>
+<testsuites><testsuite name="pytest" errors="1" failures="2" tests="5">
+<testcase classname="src.util.tests.test_foo" name="test_ok" time="0.001" />
+<testcase classname="src.util.tests.test_foo.TestFoo" name="test_fails" time="0.001">
+  <failure message="assert 1 == 2">...</failure>
+</testcase>
+<testcase classname="src.util.tests.test_bar" name="test_ok" time="0.001"></testcase>
+<testcase classname="tests.test_bar" file="src/util/tests/test_bar.py" name="test_errors">
+  <error message="fixture failed">...</error>
+</testcase>
+<testcase classname="src.util.tests.test_baz" name="test_skipped"><skipped /></testcase>
+<testcase classname="src.other.test_qux" name="test_fails"><failure /></testcase>
+</testsuite></testsuites>"#;
+
+        assert_eq!(
+            failed_files(report, |f| known_files.contains(f)),
+            HashSet::from([
+                "src/util/tests/test_foo.py".to_string(),
+                "src/util/tests/test_bar.py".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_classname_to_file() {
+        let known_files = |f: &str| f == "tests/test_foo.py";
+        assert_eq!(
+            classname_to_file("tests.test_foo", known_files),
+            Some("tests/test_foo.py".to_string())
+        );
+        assert_eq!(
+            classname_to_file("tests.test_foo.TestFoo.TestNested", known_files),
+            Some("tests/test_foo.py".to_string())
+        );
+        assert_eq!(classname_to_file("tests.test_bar", known_files), None);
+    }
+}
@@ -1,159 +1,770 @@
-use notify::event::{CreateKind, ModifyKind, RemoveKind};
+use notify::event::{CreateKind, ModifyKind, RemoveKind, RenameMode};
 use notify::EventKind::{Create, Modify, Remove};
 use notify::{Event, RecursiveMode, Result as WatcherResult, Watcher};
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::env;
 use std::ffi::OsStr;
-use std::path::Path;
-use std::process::Command;
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
 use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-pub(crate) fn run_watcher(
-    cmd: Vec<String>,
-    paths_glob: String,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let paths: Vec<_> = paths_glob.split(",").collect();
-    let cmd_name = OsStr::new(cmd.first().expect("Command must be provided"));
-    let cmd_args = &cmd[1..];
-
-    let cwd = env::current_dir()?.to_string_lossy().into_owned() + "/";
-
-    let (tx, rx) = mpsc::channel::<WatcherResult<Event>>();
-    let mut watcher = notify::recommended_watcher(tx)?;
-
-    // maintaining both a dependents graph and dependency graph since:
-    // * the dependents graph directly powers the basic functionality
-    // * the dependency graph allows us to monitor which edges were removed in a
-    //   file change without traversing the entire graph
-    println!("\x1b[93mConstructing initial graph ...\x1b[0m");
-    let mut import_map_dependents = super::ruff_util::ruff_graph(false, false, None);
-    let mut import_map_dependencies = super::ruff_util::ruff_graph(false, true, None);
-
-    watcher.watch(Path::new("."), RecursiveMode::Recursive)?;
-    println!("\x1b[93mListening! Ctrl-C to quit.\x1b[0m");
-    for res in rx {
-        match res {
-            Ok(event) => match event.kind {
-                Modify(ModifyKind::Name(_))
-                | Modify(ModifyKind::Data(_))
-                | Create(CreateKind::File)
-                | Remove(RemoveKind::File) => {
-                    // we only want to rerun analyze on files that changed, specifically either
-                    // files already tracked by the import map, or if they're python files, or
-                    // if it's a project/ruff configuration
-                    let changed_paths = event
-                        .paths
-                        .into_iter()
-                        .filter_map(|p| {
-                            let sp = p.to_str().unwrap();
-                            // a non-python file might be a dependent explicitly declared
-                            // `include-dependencies`; if so, we want to track its changes
-                            if import_map_dependents.contains_key(sp)
-                                // there might be a new python file
-                                || sp.ends_with(".py")
-                                // or a change to the config itself
-                                || sp.ends_with("ruff.toml")
-                                || sp.ends_with(".ruff.toml")
-                                || sp.ends_with("pyproject.toml")
-                            {
-                                return Some(sp.strip_prefix(&cwd).unwrap().to_string());
-                            }
-                            None
-                        })
-                        .collect::<Vec<String>>();
-
-                    if changed_paths.is_empty() {
-                        continue;
-                    }
+use serde_json::json;
 
-                    println!("Changed paths: {}", changed_paths.join(", "));
-
-                    // if a file has been removed, first find the impacted files before changing the
-                    // import map and losing that information; otherwise, we update the graph first -
-                    // even if there are removed edges, we can still evaluate with the updated graph
-                    // because for a file to be impacted by it, there must be some file in its path
-                    // (possibly itself) that was modified, which will still trigger it
-                    if event.kind != Remove(RemoveKind::Any) {
-                        // TODO: if config file changed, reconstruct entire graph; this could be
-                        // optimized by just adding new edges from include-dependencies, but
-                        // in pathological cases, `src` and such might be modified as well
-                        let import_map_dependencies_update =
-                            super::ruff_util::ruff_graph(false, false, Some(changed_paths.clone()));
-
-                        for (path, new_dependencies) in import_map_dependencies_update.iter() {
-                            let old_dependencies = import_map_dependencies
-                                .insert(path.clone(), new_dependencies.clone());
-                            // handle removed edges
-                            if let Some(old_dependencies) = old_dependencies {
-                                for m in old_dependencies.difference(new_dependencies) {
-                                    if import_map_dependents.contains_key(m) {
-                                        import_map_dependents.entry(m.clone()).and_modify(|curr| {
-                                            curr.remove(path);
-                                        });
-                                    }
-                                }
-                            }
-                            // add new edges
-                            for m in new_dependencies.iter() {
-                                let values = import_map_dependents.entry(m.clone()).or_default();
-                                values.insert(path.clone());
-                            }
-                        }
-                    }
+use super::events::{EventFormat, Events};
+use super::junit;
+use super::ruff_util::{ruff_graph, Direction};
+use super::test_selection::{is_conftest, Selection, TestMatcher};
+
+/// Prints human-readable output, which moves to stderr when stdout carries events
+macro_rules! say {
+    ($events:expr) => {
+        say!($events, "")
+    };
+    ($events:expr, $($arg:tt)*) => {
+        if $events.uses_stdout() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+/// How long to wait for the other half of a rename before treating the file as moved
+/// out of the watched tree; backends that pair renames emit both halves together
+const RENAME_PAIRING_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The import graph, kept up to date incrementally as files change
+///
+/// maintaining both a dependents graph and dependency graph since:
+/// * the dependents graph directly powers the basic functionality
+/// * the dependency graph allows us to monitor which edges were removed in a
+///   file change without traversing the entire graph
+pub(crate) struct LiveGraph {
+    dependents: HashMap<String, HashSet<String>>,
+    dependencies: HashMap<String, HashSet<String>>,
+}
+
+impl LiveGraph {
+    pub(crate) fn construct() -> Self {
+        LiveGraph {
+            dependents: ruff_graph(false, Direction::Dependents, None),
+            dependencies: ruff_graph(false, Direction::Dependencies, None),
+        }
+    }
+
+    /// Builds a graph from a list of files and their imports, instead of from ruff
+    #[cfg(test)]
+    pub(crate) fn from_dependencies(dependencies: &[(&str, &[&str])]) -> Self {
+        let mut graph = LiveGraph {
+            dependents: HashMap::new(),
+            dependencies: HashMap::new(),
+        };
+        for (file, imports) in dependencies {
+            graph.dependents.entry(file.to_string()).or_default();
+            graph.dependencies.insert(
+                file.to_string(),
+                imports.iter().map(|i| i.to_string()).collect(),
+            );
+            for import in imports.iter() {
+                graph
+                    .dependents
+                    .entry(import.to_string())
+                    .or_default()
+                    .insert(file.to_string());
+            }
+        }
+        graph
+    }
+
+    /// Each file, mapped to the files it imports
+    pub(crate) fn dependencies(&self) -> &HashMap<String, HashSet<String>> {
+        &self.dependencies
+    }
+
+    /// Each file, mapped to the files that import it
+    pub(crate) fn dependents(&self) -> &HashMap<String, HashSet<String>> {
+        &self.dependents
+    }
+
+    pub(crate) fn contains(&self, path: &str) -> bool {
+        self.dependents.contains_key(path)
+    }
 
-                    let affected_files =
-                        get_affected_files(&changed_paths, import_map_dependents.clone())
-                            .into_iter()
-                            .filter(|p| {
-                                import_map_dependents.contains_key(p)
-                                    && paths.iter().any(|args_path| p.starts_with(args_path))
-                            })
-                            .collect::<Vec<String>>();
-
-                    if event.kind == Remove(RemoveKind::File) {
-                        // remove node and all edges to it in both graphs
-                        for p in changed_paths.into_iter() {
-                            let _ = import_map_dependents.remove(&p);
-                            let old_dependencies = import_map_dependencies.remove(&p);
-                            if let Some(old_dependencies) = old_dependencies {
-                                for m in old_dependencies.iter() {
-                                    import_map_dependents.entry(m.clone()).and_modify(|curr| {
-                                        curr.remove(&p);
-                                    });
-                                }
-                            }
-                        }
+    /// The files in the graph under the directory `dir`, sorted
+    fn files_under(&self, dir: &str) -> Vec<String> {
+        let prefix = format!("{}/", dir);
+        let mut files = self
+            .dependents
+            .keys()
+            .filter(|p| p.starts_with(&prefix))
+            .cloned()
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    }
+
+    /// Each file `from` moves to `to`: just `from` for a file, and every file under it
+    /// for a directory
+    fn moves(&self, from: &str, to: &str) -> Vec<(String, String)> {
+        if self.contains(from) {
+            return vec![(from.to_string(), to.to_string())];
+        }
+        self.files_under(from)
+            .into_iter()
+            .map(|file| {
+                let moved = format!("{}{}", to, &file[from.len()..]);
+                (file, moved)
+            })
+            .collect()
+    }
+
+    /// Whether a change to `path` could change the graph
+    fn is_relevant(&self, path: &str) -> bool {
+        // a non-python file might be a dependent explicitly declared
+        // `include-dependencies`; if so, we want to track its changes
+        self.contains(path)
+            // there might be a new python file
+            || path.ends_with(".py")
+            // or a change to the config itself
+            || path.ends_with("ruff.toml")
+            || path.ends_with(".ruff.toml")
+            || path.ends_with("pyproject.toml")
+    }
+
+    /// Drops the paths in `change` that can't change the graph, or the whole change if none
+    /// are left
+    pub(crate) fn relevant_change(&self, change: Change) -> Option<Change> {
+        let relevant = |paths: Vec<String>| -> Option<Vec<String>> {
+            let paths = paths
+                .into_iter()
+                .filter(|p| self.is_relevant(p))
+                .collect::<Vec<_>>();
+            (!paths.is_empty()).then_some(paths)
+        };
+        match change {
+            Change::Modified(paths) => relevant(paths).map(Change::Modified),
+            // a removed directory removes every file under it
+            Change::Removed(paths) => relevant(
+                paths
+                    .into_iter()
+                    .flat_map(|p| match self.files_under(&p) {
+                        files if files.is_empty() => vec![p],
+                        files => files,
+                    })
+                    .collect(),
+            )
+            .map(Change::Removed),
+            Change::Renamed(from, to) => match (self.is_relevant(&from), self.is_relevant(&to)) {
+                (true, true) => Some(Change::Renamed(from, to)),
+                (true, false) => Some(Change::Removed(vec![from])),
+                (false, _) if !self.files_under(&from).is_empty() => {
+                    Some(Change::Renamed(from, to))
+                }
+                (false, true) => Some(Change::Modified(vec![to])),
+                (false, false) => None,
+            },
+        }
+    }
+
+    /// Updates the graph for `change`, and returns the files affected by it
+    pub(crate) fn apply(&mut self, change: Change) -> HashSet<String> {
+        match change {
+            Change::Modified(changed_paths) => {
+                // we update the graph first - even if there are removed edges, we can still
+                // evaluate with the updated graph because for a file to be impacted by it,
+                // there must be some file in its path (possibly itself) that was modified,
+                // which will still trigger it
+                self.update(&changed_paths);
+                self.affected_files(&changed_paths)
+            }
+            Change::Removed(removed_paths) => {
+                // first find the impacted files before changing the graph and losing that
+                // information
+                let affected_files = self.affected_files(&removed_paths);
+                self.remove(&removed_paths);
+                affected_files
+            }
+            Change::Renamed(from, to) => {
+                // anything that depended on the old paths may now be broken, so collect
+                // those before the move, then re-analyze the old importers along with
+                // the moved files themselves, whose own imports may resolve differently now
+                let (old_paths, new_paths): (Vec<_>, Vec<_>) =
+                    self.moves(&from, &to).into_iter().unzip();
+                let mut affected_files = self.affected_files(&old_paths);
+                let mut importers = HashSet::new();
+                for (old_path, new_path) in old_paths.iter().zip(new_paths.iter()) {
+                    importers.extend(self.rename(old_path, new_path));
+                }
+                // importers that moved along are re-analyzed at their new path
+                let mut reanalyze = importers
+                    .into_iter()
+                    .filter(|p| !old_paths.contains(p))
+                    .chain(new_paths.iter().cloned())
+                    .collect::<Vec<_>>();
+                reanalyze.sort();
+                reanalyze.dedup();
+                self.update(&reanalyze);
+                affected_files.extend(self.affected_files(&new_paths));
+                affected_files
+            }
+        }
+    }
+
+    /// Re-analyzes `paths` and updates their edges
+    pub(crate) fn update(&mut self, paths: &[String]) {
+        // TODO: if config file changed, reconstruct entire graph; this could be
+        // optimized by just adding new edges from include-dependencies, but
+        // in pathological cases, `src` and such might be modified as well
+        let dependencies_update = ruff_graph(false, Direction::Dependencies, Some(paths.to_vec()));
+
+        for (path, new_dependencies) in dependencies_update.iter() {
+            let old_dependencies = self
+                .dependencies
+                .insert(path.clone(), new_dependencies.clone());
+            // handle removed edges
+            if let Some(old_dependencies) = old_dependencies {
+                for m in old_dependencies.difference(new_dependencies) {
+                    if self.dependents.contains_key(m) {
+                        self.dependents.entry(m.clone()).and_modify(|curr| {
+                            curr.remove(path);
+                        });
                     }
+                }
+            }
+            // add new edges
+            for m in new_dependencies.iter() {
+                let values = self.dependents.entry(m.clone()).or_default();
+                values.insert(path.clone());
+            }
+            self.dependents.entry(path.clone()).or_default();
+        }
+    }
+
+    /// Removes the nodes for `paths` and all edges to them in both graphs
+    pub(crate) fn remove(&mut self, paths: &[String]) {
+        for p in paths {
+            let _ = self.dependents.remove(p);
+            let old_dependencies = self.dependencies.remove(p);
+            if let Some(old_dependencies) = old_dependencies {
+                for m in old_dependencies.iter() {
+                    self.dependents.entry(m.clone()).and_modify(|curr| {
+                        curr.remove(p);
+                    });
+                }
+            }
+        }
+    }
 
-                    if affected_files.is_empty() {
-                        println!("\x1b[93mNothing to do!\x1b[0m");
-                        continue;
+    /// Moves the node for `from` and its outgoing edges to `to`; returns the files that
+    /// imported `from`, whose imports no longer resolve to it and need to be re-analyzed
+    pub(crate) fn rename(&mut self, from: &str, to: &str) -> Vec<String> {
+        let importers = self.dependents.remove(from).unwrap_or_default();
+        for importer in importers.iter() {
+            self.dependencies
+                .entry(importer.clone())
+                .and_modify(|curr| {
+                    curr.remove(from);
+                });
+        }
+
+        let dependencies = self.dependencies.remove(from).unwrap_or_default();
+        for m in dependencies.iter() {
+            self.dependents.entry(m.clone()).and_modify(|curr| {
+                curr.remove(from);
+                curr.insert(to.to_string());
+            });
+        }
+        self.dependencies.insert(to.to_string(), dependencies);
+        self.dependents.entry(to.to_string()).or_default();
+
+        importers.into_iter().collect()
+    }
+
+    pub(crate) fn edge_count(&self) -> usize {
+        self.dependencies.values().map(|d| d.len()).sum()
+    }
+
+    pub(crate) fn affected_files(&self, modified_files: &[String]) -> HashSet<String> {
+        get_affected_files(modified_files, &self.dependents)
+    }
+}
+
+/// A change to the watched tree, with paths relative to the current directory
+pub(crate) enum Change {
+    Modified(Vec<String>),
+    Removed(Vec<String>),
+    /// a file or a directory, which takes every file under it along
+    Renamed(String, String),
+}
+
+/// Turns filesystem events under the current directory into `Change`s, pairing up the two
+/// halves of renames
+///
+/// the current directory must be watched by its absolute path, which keeps event paths free
+/// of a `./` prefix that would otherwise never match the paths in ruff's graph
+pub(crate) struct ChangeTracker {
+    cwd: String,
+    /// renames seen leaving a path whose arrival hasn't been seen yet, by tracker
+    pending_renames: HashMap<usize, String>,
+}
+
+impl ChangeTracker {
+    /// `cwd` is the absolute path of the current directory, with a trailing `/`
+    pub(crate) fn new(cwd: &str) -> Self {
+        ChangeTracker {
+            cwd: cwd.to_string(),
+            pending_renames: HashMap::new(),
+        }
+    }
+
+    fn relative(&self, p: &Path) -> Option<String> {
+        p.to_str()?.strip_prefix(&self.cwd).map(str::to_string)
+    }
+
+    fn relative_all<'a>(&self, paths: impl IntoIterator<Item = &'a PathBuf>) -> Vec<String> {
+        paths.into_iter().filter_map(|p| self.relative(p)).collect()
+    }
+
+    /// How long to wait for the next event before calling `flush`, if at all
+    pub(crate) fn timeout(&self) -> Option<Duration> {
+        if self.pending_renames.is_empty() {
+            None
+        } else {
+            Some(RENAME_PAIRING_TIMEOUT)
+        }
+    }
+
+    /// Resolves renames whose other half never arrived
+    pub(crate) fn flush(&mut self) -> Option<Change> {
+        if self.pending_renames.is_empty() {
+            return None;
+        }
+        // these were moved out of the watched tree, which is a removal
+        Some(Change::Removed(
+            self.pending_renames.drain().map(|(_, p)| p).collect(),
+        ))
+    }
+
+    pub(crate) fn changes(&mut self, event: Event) -> Vec<Change> {
+        match event.kind {
+            Modify(ModifyKind::Name(RenameMode::Both)) => {
+                if let Some(tracker) = event.tracker() {
+                    self.pending_renames.remove(&tracker);
+                }
+                match (
+                    event.paths.first().and_then(|p| self.relative(p)),
+                    event.paths.get(1).and_then(|p| self.relative(p)),
+                ) {
+                    (Some(from), Some(to)) => vec![Change::Renamed(from, to)],
+                    _ => vec![],
+                }
+            }
+            // the `Both` event that pairs these up follows, if the backend tracks renames
+            Modify(ModifyKind::Name(RenameMode::From)) if event.tracker().is_some() => {
+                if let Some(from) = event.paths.first().and_then(|p| self.relative(p)) {
+                    self.pending_renames.insert(event.tracker().unwrap(), from);
+                }
+                vec![]
+            }
+            Modify(ModifyKind::Name(RenameMode::To))
+                if event
+                    .tracker()
+                    .is_some_and(|t| self.pending_renames.contains_key(&t)) =>
+            {
+                vec![]
+            }
+            // otherwise, each half of a rename is either a file appearing or disappearing
+            Modify(ModifyKind::Name(_)) => {
+                let (existing, missing): (Vec<_>, Vec<_>) =
+                    event.paths.iter().partition(|p| p.exists());
+                vec![
+                    Change::Removed(self.relative_all(missing)),
+                    Change::Modified(self.relative_all(existing)),
+                ]
+            }
+            Modify(ModifyKind::Data(_)) | Create(CreateKind::File) => {
+                vec![Change::Modified(self.relative_all(&event.paths))]
+            }
+            Remove(RemoveKind::File) => vec![Change::Removed(self.relative_all(&event.paths))],
+            _ => vec![],
+        }
+    }
+}
+
+/// Input to the watch loop: either a filesystem event, or a line typed on stdin
+enum LiveEvent {
+    Fs(WatcherResult<Event>),
+    Key(String),
+}
+
+const KEYS_HELP: &str =
+    "Controls are line-based, type one then Enter: a = run all, f = run failures, (empty) = repeat last run, p = pause/resume, q = quit";
+
+/// Keeps the graph up to date with changes, and runs the command on affected files
+struct LiveRunner<'a> {
+    graph: LiveGraph,
+    paths: Vec<&'a str>,
+    select: Selection,
+    test_matcher: TestMatcher,
+    cmd: &'a [String],
+    junit_xml: Option<PathBuf>,
+    /// the files of the most recent run
+    last_run: Option<Vec<String>>,
+    /// files that failed when they last ran; with a JUnit report these are always re-run,
+    /// and run first, otherwise they're just the files of the last run, if it failed
+    failures: HashSet<String>,
+    /// while paused, the graph is kept up to date, but affected files are only collected
+    paused: Option<HashSet<String>>,
+    events: Events,
+}
+
+impl LiveRunner<'_> {
+    fn handle(&mut self, change: Change) {
+        let Some(change) = self.graph.relevant_change(change) else {
+            return;
+        };
+        match &change {
+            Change::Modified(changed_paths) => {
+                say!(self.events, "Changed paths: {}", changed_paths.join(", "));
+                self.events.emit(
+                    "change",
+                    json!({"kind": "modified", "paths": changed_paths}),
+                );
+            }
+            Change::Removed(removed_paths) => {
+                say!(self.events, "Removed paths: {}", removed_paths.join(", "));
+                self.events
+                    .emit("change", json!({"kind": "removed", "paths": removed_paths}));
+            }
+            Change::Renamed(from, to) => {
+                say!(self.events, "Renamed path: {} -> {}", from, to);
+                self.events
+                    .emit("change", json!({"kind": "renamed", "from": from, "to": to}));
+            }
+        }
+        let affected_files = self.graph.apply(change);
+        self.emit_graph_updated();
+
+        match &mut self.paused {
+            Some(paused_affected_files) => {
+                say!(
+                    self.events,
+                    "\x1b[93mPaused; affected files will run on resume\x1b[0m"
+                );
+                paused_affected_files.extend(affected_files);
+            }
+            None => {
+                self.run(affected_files);
+            }
+        }
+    }
+
+    fn emit_graph_updated(&self) {
+        self.events.emit(
+            "graph_updated",
+            json!({"files": self.graph.dependents.len(), "edges": self.graph.edge_count()}),
+        );
+    }
+
+    /// Handles a line typed on stdin; returns false if live mode should quit
+    fn handle_key(&mut self, key: &str) -> bool {
+        match key {
+            "a" => {
+                self.run_all();
+            }
+            "f" => {
+                let mut failures = self
+                    .failures
+                    .iter()
+                    .filter(|f| self.graph.contains(f))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if failures.is_empty() {
+                    say!(self.events, "\x1b[93mNo failures to re-run!\x1b[0m");
+                } else {
+                    failures.sort();
+                    say!(self.events, "Re-running failures: {}", failures.join(", "));
+                    self.execute(failures);
+                }
+            }
+            "" => match &self.last_run {
+                Some(files) => {
+                    say!(self.events, "Repeating last run");
+                    self.execute(files.clone());
+                }
+                None => say!(self.events, "\x1b[93mNothing has run yet!\x1b[0m"),
+            },
+            "p" => match self.paused.take() {
+                Some(paused_affected_files) => {
+                    say!(self.events, "\x1b[93mResumed!\x1b[0m");
+                    if !paused_affected_files.is_empty() {
+                        self.run(paused_affected_files);
                     }
+                }
+                None => {
+                    say!(
+                        self.events,
+                        "\x1b[93mPaused! Press p again to resume.\x1b[0m"
+                    );
+                    self.paused = Some(HashSet::new());
+                }
+            },
+            "q" => return false,
+            _ => say!(self.events, "{}", KEYS_HELP),
+        }
+        true
+    }
+
+    /// Runs the command on every file matching `--paths`
+    fn run_all(&mut self) -> Option<ExitStatus> {
+        say!(self.events, "Running on all files");
+        self.run(self.graph.dependents.keys().cloned().collect())
+    }
 
-                    println!("Transitively affected files: {}", affected_files.join(", "));
-                    println!();
-                    println!("\x1b[93mRUNNING COMMAND!\x1b[0m");
-                    println!();
-                    Command::new(cmd_name)
-                        .args(cmd_args)
-                        .args(affected_files)
-                        .status()
-                        .expect("failed to execute process");
-                    println!();
-                    println!("\x1b[93mCOMPLETED RUN!\x1b[0m");
-                    println!();
-                }
-                _ => continue,
+    /// Runs the command on the files affected by `changed_paths`, with the graph as is
+    fn run_changed(&mut self, changed_paths: &[String]) -> Option<ExitStatus> {
+        say!(self.events, "Changed paths: {}", changed_paths.join(", "));
+        self.events.emit(
+            "change",
+            json!({"kind": "modified", "paths": changed_paths}),
+        );
+        self.run(self.graph.affected_files(changed_paths))
+    }
+
+    /// Runs the command on the selected subset of `affected_files`; returns `None` if
+    /// nothing was selected
+    fn run(&mut self, mut affected_files: HashSet<String>) -> Option<ExitStatus> {
+        if self.select == Selection::Tests {
+            let conftests = affected_files
+                .iter()
+                .filter(|p| is_conftest(p))
+                .cloned()
+                .collect::<Vec<String>>();
+            for conftest in conftests {
+                affected_files.extend(
+                    self.test_matcher
+                        .conftest_dependents(&conftest, self.graph.dependents.keys()),
+                );
+            }
+        }
+        // previous failures are always included, so a fix gets verified even if it was
+        // made somewhere that doesn't affect them; without a report, a failed run only
+        // says that some of its files fail, and including them all would snowball
+        if self.junit_xml.is_some() {
+            affected_files.extend(self.failures.iter().cloned());
+        }
+        let mut affected_files = affected_files
+            .into_iter()
+            .filter(|p| {
+                self.graph.contains(p)
+                    && self.paths.iter().any(|args_path| p.starts_with(args_path))
+                    && (self.select == Selection::All || self.test_matcher.is_test_file(p))
+            })
+            .collect::<Vec<String>>();
+        // run previous failures first, for faster feedback on whether they're fixed
+        affected_files.sort_by_key(|p| !self.failures.contains(p));
+        self.events
+            .emit("affected", json!({"files": affected_files}));
+
+        if affected_files.is_empty() {
+            say!(self.events, "\x1b[93mNothing to do!\x1b[0m");
+            return None;
+        }
+
+        say!(
+            self.events,
+            "Transitively affected files: {}",
+            affected_files.join(", ")
+        );
+        Some(self.execute(affected_files))
+    }
+
+    fn execute(&mut self, files: Vec<String>) -> ExitStatus {
+        say!(self.events);
+        say!(self.events, "\x1b[93mRUNNING COMMAND!\x1b[0m");
+        say!(self.events);
+        // a leftover report would be mistaken for this run's if the command crashes early
+        if let Some(junit_xml) = &self.junit_xml {
+            let _ = fs::remove_file(junit_xml);
+        }
+        self.events.emit(
+            "command_started",
+            json!({"command": self.cmd, "files": files}),
+        );
+        let started = Instant::now();
+        let mut command = Command::new(OsStr::new(&self.cmd[0]));
+        // stdin is where the controls are typed, so the command doesn't get to read it
+        command
+            .args(&self.cmd[1..])
+            .args(&files)
+            .stdin(Stdio::null());
+        if self.events.uses_stdout() {
+            command.stdout(io::stderr());
+        }
+        let status = command.status().expect("failed to execute process");
+        let duration = started.elapsed();
+        say!(self.events);
+        say!(self.events, "\x1b[93mCOMPLETED RUN!\x1b[0m");
+        say!(self.events);
+
+        match self.reported_failures() {
+            // files that didn't run keep failing until they do
+            Some(failed_files) => {
+                for file in files.iter() {
+                    self.failures.remove(file);
+                }
+                self.failures.extend(failed_files);
+            }
+            None if status.success() => self.failures.clear(),
+            None => self.failures = files.iter().cloned().collect(),
+        }
+        if !self.failures.is_empty() {
+            let mut failures = self.failures.iter().cloned().collect::<Vec<_>>();
+            failures.sort();
+            say!(self.events, "Failing files: {}", failures.join(", "));
+            say!(self.events);
+        }
+        let mut failures = self.failures.iter().collect::<Vec<_>>();
+        failures.sort();
+        self.events.emit(
+            "command_finished",
+            json!({
+                "exit_code": status.code(),
+                "duration_ms": duration.as_millis() as u64,
+                "failures": failures,
+            }),
+        );
+        self.last_run = Some(files);
+        status
+    }
+
+    /// The files that failed in the JUnit report of the run that just finished, if it
+    /// wrote one
+    fn reported_failures(&self) -> Option<HashSet<String>> {
+        let report = fs::read_to_string(self.junit_xml.as_ref()?).ok()?;
+        Some(junit::failed_files(&report, |f| self.graph.contains(f)))
+    }
+}
+
+/// Watches for changes and runs the command on the affected files, until interrupted; with
+/// `--once`, returns the exit code of the initial run instead
+pub(crate) fn run_watcher(options: super::LiveOptions) -> Result<i32, Box<dyn std::error::Error>> {
+    if options.cmd.is_empty() {
+        return Err("a command to run must be provided, e.g. `ruff-tools live -- pytest`".into());
+    }
+    if options.once && !options.run_on_start && options.changed.is_empty() {
+        return Err("--once needs an initial run: pass --run-on-start or --changed".into());
+    }
+
+    let cwd_path = env::current_dir()?;
+    let cwd = cwd_path.to_string_lossy().into_owned() + "/";
+
+    let events = match (&options.events, &options.events_socket) {
+        (None, _) => Events::disabled(),
+        (Some(EventFormat::Ndjson), None) => Events::stdout(),
+        (Some(EventFormat::Ndjson), Some(socket)) => Events::socket(socket)?,
+    };
+
+    say!(events, "\x1b[93mConstructing initial graph ...\x1b[0m");
+    let mut runner = LiveRunner {
+        graph: LiveGraph::construct(),
+        paths: options.paths.split(",").collect(),
+        select: options.select,
+        test_matcher: TestMatcher::new(&options.test_patterns),
+        cmd: &options.cmd,
+        junit_xml: options.junit_xml.as_ref().map(PathBuf::from),
+        last_run: None,
+        failures: HashSet::new(),
+        paused: None,
+        events,
+    };
+    runner.emit_graph_updated();
+
+    let status = if options.run_on_start {
+        runner.run_all()
+    } else if !options.changed.is_empty() {
+        let changed_paths = options
+            .changed
+            .iter()
+            .map(|p| normalize_path(&cwd, p))
+            .collect::<Vec<_>>();
+        runner.run_changed(&changed_paths)
+    } else {
+        None
+    };
+    if options.once {
+        return Ok(status.map_or(0, |s| s.code().unwrap_or(1)));
+    }
+
+    let (tx, rx) = mpsc::channel::<LiveEvent>();
+    let fs_tx = tx.clone();
+    let mut watcher = notify::recommended_watcher(move |res| {
+        let _ = fs_tx.send(LiveEvent::Fs(res));
+    })?;
+    watcher.watch(&cwd_path, RecursiveMode::Recursive)?;
+    say!(runner.events, "\x1b[93mListening! Ctrl-C to quit.\x1b[0m");
+    say!(runner.events, "{}", KEYS_HELP);
+
+    // stdin is read line by line, without raw mode, on its own thread so it can share the
+    // event loop; if it's closed (e.g. not a terminal), live mode just keeps watching
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if tx.send(LiveEvent::Key(line.trim().to_string())).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut tracker = ChangeTracker::new(&cwd);
+    loop {
+        let res = match tracker.timeout() {
+            None => match rx.recv() {
+                Ok(res) => res,
+                Err(_) => break,
+            },
+            Some(timeout) => match rx.recv_timeout(timeout) {
+                Ok(res) => res,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    if let Some(change) = tracker.flush() {
+                        runner.handle(change);
+                    }
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
             },
-            Err(_) => continue,
         };
+        match res {
+            LiveEvent::Key(key) => {
+                if !runner.handle_key(&key) {
+                    break;
+                }
+            }
+            LiveEvent::Fs(Ok(event)) => {
+                for change in tracker.changes(event) {
+                    runner.handle(change);
+                }
+            }
+            LiveEvent::Fs(Err(_)) => continue,
+        }
     }
-    Ok(())
+    Ok(0)
+}
+
+/// Turns paths passed on the command line, which may be absolute or start with `./`,
+/// into paths relative to `cwd` like the ones in ruff's graph
+pub(crate) fn normalize_path(cwd: &str, path: &str) -> String {
+    let path = path.strip_prefix(cwd).unwrap_or(path);
+    path.strip_prefix("./").unwrap_or(path).to_string()
 }
 
-fn get_affected_files(
+pub(crate) fn get_affected_files(
     modified_files: &[String],
-    import_map_dependents: HashMap<String, HashSet<String>>,
+    import_map_dependents: &HashMap<String, HashSet<String>>,
 ) -> HashSet<String> {
     // run a plain BFS of the dependents graph; all visited nodes are affected files
     let mut visited: HashSet<String> = HashSet::new();
@@ -175,3 +786,84 @@ fn get_affected_files(
 
     visited
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rename() {
+        let mut graph = LiveGraph::from_dependencies(&[
+            ("a.py", &["b.py"]),
+            ("b.py", &["c.py"]),
+            ("c.py", &[]),
+            ("test_a.py", &["a.py"]),
+        ]);
+        assert_eq!(graph.rename("b.py", "d.py"), ["a.py"]);
+
+        assert!(!graph.contains("b.py"));
+        assert!(!graph.dependencies.contains_key("b.py"));
+        // the old importer no longer resolves its import, until it's re-analyzed
+        assert_eq!(graph.dependencies["a.py"], HashSet::new());
+        // the moved file keeps its own imports
+        assert_eq!(
+            graph.dependencies["d.py"],
+            HashSet::from(["c.py".to_string()])
+        );
+        assert_eq!(
+            graph.dependents["c.py"],
+            HashSet::from(["d.py".to_string()])
+        );
+        assert_eq!(graph.dependents["d.py"], HashSet::new());
+        assert_eq!(
+            graph.affected_files(&["c.py".to_string()]),
+            HashSet::from(["c.py".to_string(), "d.py".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_directory_changes() {
+        let graph = LiveGraph::from_dependencies(&[
+            ("main.py", &["pkg/a.py"]),
+            ("pkg/a.py", &["pkg/sub/b.py"]),
+            ("pkg/sub/b.py", &[]),
+            ("pkg2.py", &[]),
+        ]);
+        assert_eq!(
+            graph.moves("pkg", "lib/pkg"),
+            [
+                ("pkg/a.py".to_string(), "lib/pkg/a.py".to_string()),
+                ("pkg/sub/b.py".to_string(), "lib/pkg/sub/b.py".to_string()),
+            ]
+        );
+        assert!(matches!(
+            graph.relevant_change(Change::Renamed("pkg".to_string(), "lib".to_string())),
+            Some(Change::Renamed(..))
+        ));
+        assert!(matches!(
+            graph.relevant_change(Change::Removed(vec!["pkg/sub".to_string()])),
+            Some(Change::Removed(paths)) if paths == ["pkg/sub/b.py"]
+        ));
+        // untracked directories are still irrelevant
+        assert!(graph
+            .relevant_change(Change::Renamed("docs".to_string(), "doc".to_string()))
+            .is_none());
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut graph = LiveGraph::from_dependencies(&[
+            ("a.py", &["b.py"]),
+            ("b.py", &["c.py"]),
+            ("c.py", &[]),
+        ]);
+        graph.remove(&["b.py".to_string()]);
+
+        assert!(!graph.contains("b.py"));
+        assert_eq!(graph.dependents["c.py"], HashSet::new());
+        assert_eq!(
+            graph.affected_files(&["c.py".to_string()]),
+            HashSet::from(["c.py".to_string()])
+        );
+    }
+}
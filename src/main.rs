@@ -1,24 +1,247 @@
+mod blast_radius;
+mod compare_cycles;
+mod config;
 mod cycle_detection;
+mod daemon;
+mod events;
+mod export;
+mod graph;
+mod graph_diff;
+mod imports;
+mod junit;
 mod live;
+mod metrics;
 mod minimize_cycles;
+mod report;
 mod ruff_util;
+mod sarif;
+mod test_selection;
+mod unreachable;
 
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
 pub struct MinimizeCyclesOptions {
+    /// Cycles to minimize: pylint's output (text, `json` or `json2`), the SARIF output
+    /// of `detect-cycles`, or one `a -> b -> c` per line
     #[structopt(short, long)]
     cycle_results_file: String,
+    /// List every distinct chordless cycle each cycle can be minimized to, instead of
+    /// only the one reached by always taking the chord that cuts the most
+    #[structopt(long)]
+    all_minimal: bool,
 }
 
 #[derive(StructOpt, Debug)]
-pub struct CycleDetectionOptions {}
+pub struct CycleDetectionOptions {
+    /// Output format: "text" (the default), or "sarif" for code scanning tools
+    #[structopt(short, long)]
+    format: Option<cycle_detection::CycleFormat>,
+    /// Leave out imports that don't run when a module is imported: those under
+    /// `if TYPE_CHECKING:` and those inside functions. Pass `--runtime-only false` to
+    /// check every import
+    #[structopt(long, parse(try_from_str), default_value = "true")]
+    runtime_only: bool,
+    /// File of known cycles, one `a.py -> b.py` per line: only other cycles are
+    /// reported, and the command fails if there are any
+    #[structopt(long)]
+    baseline: Option<String>,
+    /// Write the cycles found to the `--baseline` file instead
+    #[structopt(long, requires = "baseline")]
+    write_baseline: bool,
+    /// Imports to leave out, as `importer -> imported`, each a file or a module
+    #[structopt(long, use_delimiter = true)]
+    exclude_edges: Vec<String>,
+    /// Fail if there are more cycles than this
+    #[structopt(long)]
+    max_cycles: Option<usize>,
+    /// Fail if any cycle is longer than this
+    #[structopt(long)]
+    max_cycle_length: Option<usize>,
+    /// Fail if the lengths of all cycles add up to more than this
+    #[structopt(long)]
+    max_total_cycle_length: Option<usize>,
+    /// Fail if more modules than this all import each other, directly or indirectly
+    #[structopt(long)]
+    max_scc_size: Option<usize>,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct CompareCyclesOptions {
+    /// pylint's `cyclic-import` results, in any format `minimize-cycles` accepts
+    #[structopt(long)]
+    pylint_results: String,
+    /// Compare against the graph `detect-cycles` checks by default, without the imports
+    /// under `if TYPE_CHECKING:` or inside functions; pass `--runtime-only false` to
+    /// compare against every import
+    #[structopt(long, parse(try_from_str), default_value = "true")]
+    runtime_only: bool,
+    /// Print the comparison as JSON
+    #[structopt(long)]
+    json: bool,
+}
 
 #[derive(StructOpt, Debug)]
 pub struct LiveOptions {
     cmd: Vec<String>,
     #[structopt(default_value = "", short, long)]
     paths: String,
+    /// Which affected files to pass to the command: "all", or only "tests"
+    #[structopt(default_value = "all", long)]
+    select: test_selection::Selection,
+    /// Basename globs that identify test files, like pytest's `python_files`
+    #[structopt(default_value = "test_*.py,*_test.py", long)]
+    test_patterns: String,
+    /// Run the command on all files matching `--paths` before watching for changes
+    #[structopt(long, conflicts_with = "changed")]
+    run_on_start: bool,
+    /// Run the command on the files affected by these files before watching for changes
+    #[structopt(long, use_delimiter = true)]
+    changed: Vec<String>,
+    /// Exit after the initial run (`--run-on-start` or `--changed`) instead of watching,
+    /// with the command's exit code
+    #[structopt(long)]
+    once: bool,
+    /// JUnit XML report written by the command, such as pytest's `--junitxml`, to tell
+    /// which files failed; otherwise, every file in a failed run is considered failing
+    #[structopt(long)]
+    junit_xml: Option<String>,
+    /// Emit a machine-readable event stream ("ndjson") on stdout; human-readable output
+    /// and the command's output move to stderr
+    #[structopt(long)]
+    events: Option<events::EventFormat>,
+    /// Send the event stream to clients of a Unix socket at this path instead of stdout
+    #[structopt(long, requires = "events")]
+    events_socket: Option<String>,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct ExportOptions {
+    /// Output format: "dot" (the default), "mermaid" or "graphml"
+    #[structopt(short, long)]
+    format: Option<export::ExportFormat>,
+    /// Whether nodes are modules or files: "module" or "file"
+    #[structopt(default_value = "module", long)]
+    nodes: export::NodeKind,
+    /// File to write to, instead of stdout
+    #[structopt(short, long)]
+    output: Option<String>,
+    /// Only include this package (or directory) and the modules in it
+    #[structopt(long)]
+    subtree: Option<String>,
+    /// Collapse modules into their packages at this depth, e.g. 2 for `foo.bar`
+    #[structopt(long)]
+    depth: Option<usize>,
+    /// Only include modules that are part of cycles
+    #[structopt(long)]
+    cycles_only: bool,
+    /// Group nodes into clusters by the package they're in
+    #[structopt(long)]
+    clusters: bool,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct MetricsOptions {
+    /// Output format: "table" (the default), "csv" or "json"
+    #[structopt(short, long)]
+    format: Option<metrics::MetricsFormat>,
+    /// Column to sort by, e.g. "transitive_dependents" or "instability"
+    #[structopt(default_value = "transitive_dependents", long)]
+    sort: metrics::SortColumn,
+    /// Only show this many modules
+    #[structopt(long)]
+    limit: Option<usize>,
+    /// Compute metrics for packages instead, collapsing modules at this depth
+    #[structopt(long)]
+    package_depth: Option<usize>,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct BlastRadiusOptions {
+    /// Rank files by how many files they affect ("affected") or how many tests ("tests")
+    #[structopt(default_value = "affected", long = "rank-by")]
+    rank_by: blast_radius::RankBy,
+    /// Basename globs that identify test files, like pytest's `python_files`
+    #[structopt(default_value = "test_*.py,*_test.py", long)]
+    test_patterns: String,
+    /// Only show this many files
+    #[structopt(default_value = "20", long)]
+    limit: usize,
+    /// Print the ranking as JSON
+    #[structopt(long)]
+    json: bool,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct GraphDiffOptions {
+    /// Revision to compare from, e.g. the PR's base branch
+    rev_a: String,
+    /// Revision to compare to
+    rev_b: String,
+    /// Output format: "markdown" (the default, e.g. for a PR comment) or "json"
+    #[structopt(short, long)]
+    format: Option<graph_diff::DiffFormat>,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct UnreachableOptions {
+    /// Modules that are entrypoints, in addition to the console scripts in `--pyproject`
+    #[structopt(long, use_delimiter = true)]
+    entry: Vec<String>,
+    /// The `pyproject.toml` whose `[project.scripts]` are entrypoints, if it exists
+    #[structopt(default_value = "pyproject.toml", long)]
+    pyproject: String,
+    /// Basename globs that identify test files, which are entrypoints too
+    #[structopt(default_value = "test_*.py,*_test.py", long)]
+    test_patterns: String,
+    /// Print the results as JSON
+    #[structopt(long)]
+    json: bool,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct ReportOptions {
+    /// File to write the HTML report to
+    #[structopt(long)]
+    html: String,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct DaemonOptions {
+    /// Path of the Unix socket to serve queries on
+    #[structopt(default_value = ".ruff-tools.sock", short, long)]
+    socket: String,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct QueryOptions {
+    /// Path of the Unix socket the daemon serves queries on
+    #[structopt(default_value = ".ruff-tools.sock", short, long)]
+    socket: String,
+    /// Print the raw JSON result
+    #[structopt(long)]
+    json: bool,
+    #[structopt(subcommand)]
+    query: Query,
+}
+
+#[derive(StructOpt, Debug)]
+pub enum Query {
+    /// Files transitively affected by changes to these files
+    #[structopt(name = "affected-files")]
+    AffectedFiles { paths: Vec<String> },
+    /// Files that directly import this file
+    #[structopt(name = "dependents")]
+    Dependents { path: String },
+    /// Files directly imported by this file
+    #[structopt(name = "dependencies")]
+    Dependencies { path: String },
+    /// The shortest chain of imports from one file to another
+    #[structopt(name = "path-between")]
+    PathBetween { from: String, to: String },
+    /// Import cycles containing this file
+    #[structopt(name = "cycles")]
+    Cycles { path: String },
 }
 
 #[derive(StructOpt, Debug)]
@@ -27,23 +250,119 @@ enum RuffTools {
     CycleDetection(CycleDetectionOptions),
     #[structopt(name = "minimize-cycles")]
     MinimizeCycles(MinimizeCyclesOptions),
+    #[structopt(name = "compare-cycles")]
+    CompareCycles(CompareCyclesOptions),
     #[structopt(name = "lint-imports")]
     ImportLinter {},
     #[structopt(name = "live")]
     Live(LiveOptions),
+    #[structopt(name = "export")]
+    Export(ExportOptions),
+    #[structopt(name = "metrics")]
+    Metrics(MetricsOptions),
+    #[structopt(name = "blast-radius")]
+    BlastRadius(BlastRadiusOptions),
+    #[structopt(name = "graph-diff")]
+    GraphDiff(GraphDiffOptions),
+    #[structopt(name = "unreachable")]
+    Unreachable(UnreachableOptions),
+    #[structopt(name = "report")]
+    Report(ReportOptions),
+    #[structopt(name = "daemon")]
+    Daemon(DaemonOptions),
+    #[structopt(name = "query")]
+    Query(QueryOptions),
 }
 
 // #[structopt(short, long)]
 // detect_string_imports: bool,
 
+/// Reads the config file and fills in the options not given as flags
+fn configure(options: &mut RuffTools) -> Result<(), Box<dyn std::error::Error>> {
+    let config = config::Config::find(&std::env::current_dir()?)?;
+    ruff_util::configure(config.ruff()?, config.source_roots()?);
+    match options {
+        RuffTools::CycleDetection(cmd) => config.apply_cycle_detection(cmd)?,
+        RuffTools::Live(cmd) => config.apply_live(cmd)?,
+        RuffTools::Export(cmd) => config.apply_export(cmd)?,
+        RuffTools::Metrics(cmd) => config.apply_metrics(cmd)?,
+        RuffTools::GraphDiff(cmd) => config.apply_graph_diff(cmd)?,
+        _ => (),
+    }
+    Ok(())
+}
+
 fn main() {
-    let options = RuffTools::from_args();
+    let mut options = RuffTools::from_args();
+    if let Err(e) = configure(&mut options) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
     match options {
-        RuffTools::MinimizeCycles(cmd) => minimize_cycles::minimize_cycles(cmd.cycle_results_file),
-        RuffTools::CycleDetection(_) => cycle_detection::detect_cycles(),
-        RuffTools::Live(cmd) => {
-            let _ = live::run_watcher(cmd.cmd, cmd.paths);
+        RuffTools::MinimizeCycles(cmd) => {
+            if let Err(e) = minimize_cycles::minimize_cycles(cmd) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        RuffTools::CycleDetection(cmd) => {
+            if let Err(e) = cycle_detection::detect_cycles(cmd) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        RuffTools::CompareCycles(cmd) => {
+            if let Err(e) = compare_cycles::compare(cmd) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        RuffTools::Live(cmd) => match live::run_watcher(cmd) {
+            Ok(code) => std::process::exit(code),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        RuffTools::Export(cmd) => {
+            if let Err(e) = export::export(cmd) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        RuffTools::Metrics(cmd) => metrics::metrics(cmd),
+        RuffTools::BlastRadius(cmd) => blast_radius::blast_radius(cmd),
+        RuffTools::GraphDiff(cmd) => {
+            if let Err(e) = graph_diff::graph_diff(cmd) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        RuffTools::Unreachable(cmd) => {
+            if let Err(e) = unreachable::unreachable(cmd) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        RuffTools::Report(cmd) => {
+            if let Err(e) = report::report(cmd) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        RuffTools::Daemon(cmd) => {
+            if let Err(e) = daemon::run_daemon(cmd) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
         }
+        RuffTools::Query(cmd) => match daemon::run_query(cmd) {
+            Ok(code) => std::process::exit(code),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
         _ => (),
     }
 }
@@ -0,0 +1,316 @@
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use super::graph::{collapse_graph, reverse, strongly_connected_components, TransitiveDependents};
+use super::ruff_util::{ruff_graph, Direction};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MetricsFormat {
+    #[default]
+    Table,
+    Csv,
+    Json,
+}
+
+impl FromStr for MetricsFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(MetricsFormat::Table),
+            "csv" => Ok(MetricsFormat::Csv),
+            "json" => Ok(MetricsFormat::Json),
+            _ => Err(format!(
+                "unknown metrics format '{}', expected 'table', 'csv' or 'json'",
+                s
+            )),
+        }
+    }
+}
+
+const COLUMNS: [&str; 6] = [
+    "module",
+    "afferent",
+    "efferent",
+    "instability",
+    "transitive_dependents",
+    "chain_depth",
+];
+
+/// A column to sort by; numbers sort from largest to smallest, modules alphabetically
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortColumn {
+    Module,
+    Afferent,
+    Efferent,
+    Instability,
+    TransitiveDependents,
+    ChainDepth,
+}
+
+impl FromStr for SortColumn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "module" => Ok(SortColumn::Module),
+            "afferent" => Ok(SortColumn::Afferent),
+            "efferent" => Ok(SortColumn::Efferent),
+            "instability" => Ok(SortColumn::Instability),
+            "transitive_dependents" => Ok(SortColumn::TransitiveDependents),
+            "chain_depth" => Ok(SortColumn::ChainDepth),
+            _ => Err(format!(
+                "unknown column '{}', expected one of {}",
+                s,
+                COLUMNS.join(", ")
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct ModuleMetrics {
+    module: String,
+    /// modules that import this one (Ca)
+    afferent: usize,
+    /// modules this one imports (Ce)
+    efferent: usize,
+    /// Ce / (Ca + Ce): 0 is maximally stable, 1 maximally unstable
+    instability: f64,
+    /// modules that import this one, directly or indirectly, i.e. what a change to it
+    /// could break
+    transitive_dependents: usize,
+    /// length of the longest chain of imports starting here; modules in a cycle
+    /// count as one link
+    chain_depth: usize,
+}
+
+impl ModuleMetrics {
+    fn values(&self) -> [String; 6] {
+        [
+            self.module.clone(),
+            self.afferent.to_string(),
+            self.efferent.to_string(),
+            format!("{:.2}", self.instability),
+            self.transitive_dependents.to_string(),
+            self.chain_depth.to_string(),
+        ]
+    }
+}
+
+pub(crate) fn compute_metrics(graph: &HashMap<String, HashSet<String>>) -> Vec<ModuleMetrics> {
+    let dependents = reverse(graph);
+    let transitive_dependents = TransitiveDependents::new(graph);
+
+    // components come out with their dependencies first, so every dependency's depth
+    // is known by the time its importers get to it
+    let components = strongly_connected_components(graph);
+    let mut depths: HashMap<&str, usize> = HashMap::new();
+    for component in components.iter() {
+        let depth = component
+            .iter()
+            .flat_map(|module| graph.get(*module).into_iter().flatten())
+            .filter(|dependency| !component.contains(&dependency.as_str()))
+            .map(|dependency| depths[dependency.as_str()] + 1)
+            .max()
+            .unwrap_or(0);
+        depths.extend(component.iter().map(|module| (*module, depth)));
+    }
+
+    let mut metrics = dependents
+        .iter()
+        .map(|(module, module_dependents)| {
+            let afferent = module_dependents.len();
+            let efferent = graph.get(module).map_or(0, |d| d.len());
+            ModuleMetrics {
+                module: module.clone(),
+                afferent,
+                efferent,
+                instability: match afferent + efferent {
+                    0 => 0.0,
+                    total => efferent as f64 / total as f64,
+                },
+                transitive_dependents: transitive_dependents.count(module) - 1,
+                chain_depth: depths[module.as_str()],
+            }
+        })
+        .collect::<Vec<_>>();
+    metrics.sort_by(|a, b| a.module.cmp(&b.module));
+    metrics
+}
+
+fn sort_metrics(metrics: &mut [ModuleMetrics], column: SortColumn) {
+    metrics.sort_by(|a, b| match column {
+        SortColumn::Module => a.module.cmp(&b.module),
+        SortColumn::Afferent => b.afferent.cmp(&a.afferent),
+        SortColumn::Efferent => b.efferent.cmp(&a.efferent),
+        SortColumn::Instability => b.instability.total_cmp(&a.instability),
+        SortColumn::TransitiveDependents => b.transitive_dependents.cmp(&a.transitive_dependents),
+        SortColumn::ChainDepth => b.chain_depth.cmp(&a.chain_depth),
+    });
+}
+
+fn render(metrics: &[ModuleMetrics], format: MetricsFormat) -> String {
+    let rows = metrics.iter().map(|m| m.values()).collect::<Vec<_>>();
+    match format {
+        MetricsFormat::Table => {
+            let widths = (0..COLUMNS.len())
+                .map(|i| {
+                    rows.iter()
+                        .map(|row| row[i].len())
+                        .chain([COLUMNS[i].len()])
+                        .max()
+                        .unwrap()
+                })
+                .collect::<Vec<_>>();
+            let line = |cells: &[&str]| {
+                let mut line = format!("{:<width$}", cells[0], width = widths[0]);
+                for (cell, width) in cells.iter().zip(widths.iter()).skip(1) {
+                    line.push_str(&format!("  {:>width$}", cell, width = width));
+                }
+                line + "\n"
+            };
+            let mut table = line(&COLUMNS);
+            for row in rows.iter() {
+                table.push_str(&line(&row.each_ref().map(String::as_str)));
+            }
+            table
+        }
+        MetricsFormat::Csv => {
+            let mut csv = COLUMNS.join(",") + "\n";
+            for row in rows.iter() {
+                let cells = row.iter().map(|cell| csv_field(cell)).collect::<Vec<_>>();
+                csv.push_str(&cells.join(","));
+                csv.push('\n');
+            }
+            csv
+        }
+        MetricsFormat::Json => {
+            let objects = metrics
+                .iter()
+                .map(|m| {
+                    json!({
+                        "module": m.module,
+                        "afferent": m.afferent,
+                        "efferent": m.efferent,
+                        "instability": m.instability,
+                        "transitive_dependents": m.transitive_dependents,
+                        "chain_depth": m.chain_depth,
+                    })
+                })
+                .collect::<Vec<_>>();
+            serde_json::to_string_pretty(&objects).unwrap() + "\n"
+        }
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub(crate) fn metrics(options: super::MetricsOptions) {
+    let graph = ruff_graph(true, Direction::Dependencies, None);
+    let mut metrics = compute_metrics(&collapse_graph(&graph, options.package_depth, '.'));
+    sort_metrics(&mut metrics, options.sort);
+    if let Some(limit) = options.limit {
+        metrics.truncate(limit);
+    }
+    print!("{}", render(&metrics, options.format.unwrap_or_default()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `a` and `b` import each other, and everything ends up at `d`
+    fn graph() -> HashMap<String, HashSet<String>> {
+        HashMap::from([
+            ("a".to_string(), HashSet::from(["b".to_string()])),
+            (
+                "b".to_string(),
+                HashSet::from(["a".to_string(), "c".to_string()]),
+            ),
+            ("c".to_string(), HashSet::from(["d".to_string()])),
+            ("d".to_string(), HashSet::new()),
+            ("e".to_string(), HashSet::from(["d".to_string()])),
+        ])
+    }
+
+    fn summary(metrics: &[ModuleMetrics]) -> Vec<(&str, usize, usize, usize, usize)> {
+        metrics
+            .iter()
+            .map(|m| {
+                (
+                    m.module.as_str(),
+                    m.afferent,
+                    m.efferent,
+                    m.transitive_dependents,
+                    m.chain_depth,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compute_metrics() {
+        let metrics = compute_metrics(&graph());
+        assert_eq!(
+            summary(&metrics),
+            [
+                ("a", 1, 1, 1, 2),
+                ("b", 1, 2, 1, 2),
+                ("c", 1, 1, 2, 1),
+                ("d", 2, 0, 4, 0),
+                ("e", 0, 1, 0, 1),
+            ]
+        );
+        assert_eq!(metrics[1].instability, 2.0 / 3.0);
+        assert_eq!(metrics[3].instability, 0.0);
+        assert_eq!(metrics[4].instability, 1.0);
+    }
+
+    #[test]
+    fn test_sort_metrics() {
+        let mut metrics = compute_metrics(&graph());
+        sort_metrics(&mut metrics, "transitive_dependents".parse().unwrap());
+        assert_eq!(metrics[0].module, "d");
+        sort_metrics(&mut metrics, "module".parse().unwrap());
+        assert_eq!(metrics[0].module, "a");
+        assert!("fan_in".parse::<SortColumn>().is_err());
+        // every column can be sorted by
+        assert!(COLUMNS.iter().all(|c| c.parse::<SortColumn>().is_ok()));
+    }
+
+    #[test]
+    fn test_render() {
+        let metrics = compute_metrics(&graph())
+            .into_iter()
+            .filter(|m| m.module == "b" || m.module == "d")
+            .collect::<Vec<_>>();
+        assert_eq!(
+            render(&metrics, MetricsFormat::Table),
+            "\
+module  afferent  efferent  instability  transitive_dependents  chain_depth
+b              1         2         0.67                      1            2
+d              2         0         0.00                      4            0
+"
+        );
+        assert_eq!(
+            render(&metrics, MetricsFormat::Csv),
+            "\
+module,afferent,efferent,instability,transitive_dependents,chain_depth
+b,1,2,0.67,1,2
+d,2,0,0.00,4,0
+"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&metrics, MetricsFormat::Json)).unwrap();
+        assert_eq!(json[1]["transitive_dependents"], 4);
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+}
@@ -1,8 +1,14 @@
+use serde_json::Value;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::error::Error;
 use std::fs;
 use std::vec::Vec;
 
+use super::imports::ImportLocator;
+use super::ruff_util::{path_to_module, to_module_graph, Direction};
+
 /// Gives the length of a cycle (number of nodes) if it is shortened
 /// using an edge from vertex index i to j
 fn cycle_size(c_len: usize, i: usize, j: usize) -> usize {
@@ -40,69 +46,260 @@ fn sub_cycle<'a>(c: &[&'a str], i: usize, j: usize) -> Vec<&'a str> {
     canonical_cycle(new_cycle)
 }
 
-pub(crate) fn minimize_cycle<'a>(
-    graph: &HashMap<String, HashSet<String>>,
-    cycle: &[&'a str],
-) -> Vec<&'a str> {
-    // all the None cases can be simplified with a base case of
-    // embiggen = (-1, 0, cycle.len())
-    // but this is more explicit
-    let mut emsmallen: Option<(usize, usize, usize)> = None;
+/// The chords of `cycle`: imports between modules in it that aren't part of it, as
+/// `(i, j)` for an import from `cycle[i]` to `cycle[j]`
+fn chords(graph: &HashMap<String, HashSet<String>>, cycle: &[&str]) -> Vec<(usize, usize)> {
+    let mut chords = Vec::new();
     for i in 0..cycle.len() {
         for j in 0..cycle.len() {
             if j != i
-                && j != (i + 1)
+                && j != (i + 1) % cycle.len()
                 && graph.contains_key(cycle[i])
                 && graph.get(cycle[i]).unwrap().contains(cycle[j])
             {
-                let proposed_cycle_size = cycle_size(cycle.len(), i, j);
-                if emsmallen.is_none() || proposed_cycle_size < emsmallen.unwrap().2 {
-                    emsmallen = Some((i, j, proposed_cycle_size));
-                }
+                chords.push((i, j));
             }
         }
     }
-    match emsmallen {
-        Some((i, j, _)) => sub_cycle(cycle, i, j),
-        None => cycle.to_vec(),
+    chords
+}
+
+/// Shortens `cycle` with the chord that cuts the most, then does the same to the result,
+/// until there's no chord left, i.e. the cycle is an induced one
+pub(crate) fn minimize_cycle<'a>(
+    graph: &HashMap<String, HashSet<String>>,
+    cycle: &[&'a str],
+) -> Vec<&'a str> {
+    let mut cycle = cycle.to_vec();
+    // the first of the smallest, so ties go to the chord from the earliest module
+    while let Some((i, j)) = chords(graph, &cycle)
+        .into_iter()
+        .min_by_key(|(i, j)| cycle_size(cycle.len(), *i, *j))
+    {
+        cycle = sub_cycle(&cycle, i, j);
     }
+    cycle
 }
-pub(crate) fn minimize_cycles(cycles_results_file: String) {
-    let graph = super::ruff_util::ruff_graph(true, false, None);
-
-    let contents =
-        fs::read_to_string(cycles_results_file).expect("Should have been able to read the file");
-    let mut cycles = contents
-        .split("\n")
-        .filter_map(|l| {
-            // pylint's format is
-            // file_name:lno:cno: R0401: Cyclic import(<cycle details>) (cyclic-import)
-            l.find(" -> ").map(|_| {
-                l.split("(")
-                    .nth(1)
-                    .unwrap()
-                    .split(")")
-                    .next()
-                    .unwrap()
-                    .split(" -> ")
-                    .collect()
-            })
+
+/// Every distinct chordless cycle `cycle` can be minimized to, following each chord
+/// rather than only the one that cuts the most
+pub(crate) fn minimal_sub_cycles<'a>(
+    graph: &HashMap<String, HashSet<String>>,
+    cycle: &[&'a str],
+) -> BTreeSet<Vec<&'a str>> {
+    let mut minimal = BTreeSet::new();
+    let mut seen = HashSet::new();
+    let mut stack = vec![canonical_cycle(cycle)];
+    while let Some(cycle) = stack.pop() {
+        if !seen.insert(cycle.clone()) {
+            continue;
+        }
+        let chords = chords(graph, &cycle);
+        if chords.is_empty() {
+            minimal.insert(cycle);
+        } else {
+            stack.extend(chords.into_iter().map(|(i, j)| sub_cycle(&cycle, i, j)));
+        }
+    }
+    minimal
+}
+
+/// The imports of `cycle` that aren't in `graph`, which make it a false positive
+pub(crate) fn missing_edges<'a>(
+    graph: &HashMap<String, HashSet<String>>,
+    cycle: &[&'a str],
+) -> Vec<(&'a str, &'a str)> {
+    (0..cycle.len())
+        .map(|i| (cycle[i], cycle[(i + 1) % cycle.len()]))
+        .filter(|(from, to)| {
+            !graph
+                .get(*from)
+                .is_some_and(|imports| imports.contains(*to))
         })
+        .collect()
+}
+
+/// A cycle written as `a -> b -> c`, optionally repeating the first module at the end,
+/// with files named by their modules
+fn parse_cycle(text: &str) -> Option<Vec<String>> {
+    let mut cycle = text.split("->").map(str::trim).collect::<Vec<_>>();
+    if cycle.len() > 2 && cycle.first() == cycle.last() {
+        cycle.pop();
+    }
+    if cycle.len() < 2
+        || cycle
+            .iter()
+            .any(|m| m.is_empty() || m.contains(char::is_whitespace))
+    {
+        return None;
+    }
+    Some(
+        cycle
+            .into_iter()
+            .map(|m| match m.ends_with(".py") {
+                true => path_to_module(m),
+                false => m.to_string(),
+            })
+            .collect(),
+    )
+}
+
+/// The cycle in a pylint message, `Cyclic import (a -> b)`
+fn parse_pylint_message(message: &str) -> Option<Vec<String>> {
+    let start = message.find('(')?;
+    let end = message[start..].find(')')? + start;
+    parse_cycle(&message[start + 1..end])
+}
+
+/// Whether a pylint JSON message, from `--output-format=json` or `json2`, is a cycle
+fn is_pylint_cycle(message: &Value) -> bool {
+    message["symbol"] == "cyclic-import"
+        || message["message-id"] == "R0401"
+        || message["messageId"] == "R0401"
+}
+
+/// The cycle in a result of `detect-cycles --format sarif`
+fn sarif_cycle(result: &Value) -> Option<Vec<String>> {
+    let fingerprint = result["partialFingerprints"]["importCycle/v1"].as_str();
+    let message = || {
+        result["message"]["text"]
+            .as_str()?
+            .strip_prefix("Import cycle:")
+    };
+    parse_cycle(fingerprint.or_else(message)?)
+}
+
+/// The cycles in `contents`, which is pylint's output in its default text format or as
+/// `json` or `json2`, the SARIF output of `detect-cycles`, or one `a -> b -> c` per line;
+/// along with the lines or entries that couldn't be read
+pub(crate) fn parse_cycles(contents: &str) -> (Vec<Vec<String>>, Vec<String>) {
+    let mut cycles = Vec::new();
+    let mut errors = Vec::new();
+    if !contents.trim_start().starts_with(['[', '{']) {
+        for (i, line) in contents.lines().enumerate() {
+            // pylint also prints headers, scores and other messages
+            if !line.contains("->") {
+                continue;
+            }
+            let is_pylint = line.contains("cyclic-import") || line.contains("R0401");
+            let cycle = match is_pylint {
+                true => parse_pylint_message(line),
+                false => parse_cycle(line),
+            };
+            match cycle {
+                Some(cycle) => cycles.push(cycle),
+                None if is_pylint => errors.push(format!(
+                    "line {}: no cycle in pylint message '{}'",
+                    i + 1,
+                    line
+                )),
+                None => errors.push(format!(
+                    "line {}: expected a cycle like 'a -> b -> c', got '{}'",
+                    i + 1,
+                    line
+                )),
+            }
+        }
+        return (cycles, errors);
+    }
+
+    let json = match serde_json::from_str::<Value>(contents) {
+        Ok(json) => json,
+        Err(e) => {
+            return (
+                cycles,
+                vec![format!("line {}: invalid JSON: {}", e.line(), e)],
+            )
+        }
+    };
+    // each cycle found, or the entry that couldn't be read, numbered by where the entry is
+    // among all of the file's messages or results, cycles or not
+    let entries: Vec<(usize, Result<Vec<String>, &Value>)> =
+        if let Some(messages) = json.as_array().or(json["messages"].as_array()) {
+            messages
+                .iter()
+                .enumerate()
+                .filter(|(_, m)| is_pylint_cycle(m))
+                .map(|(i, m)| {
+                    let cycle = m["message"].as_str().and_then(parse_pylint_message);
+                    (i, cycle.ok_or(m))
+                })
+                .collect()
+        } else if let Some(runs) = json["runs"].as_array() {
+            runs.iter()
+                .filter_map(|run| run["results"].as_array())
+                .flatten()
+                .enumerate()
+                .filter(|(_, result)| result["ruleId"] == super::sarif::CYCLIC_IMPORT.id)
+                .map(|(i, result)| (i, sarif_cycle(result).ok_or(result)))
+                .collect()
+        } else {
+            let error = "expected pylint's JSON output or a SARIF log".to_string();
+            return (cycles, vec![error]);
+        };
+    for (i, entry) in entries {
+        match entry {
+            Ok(cycle) => cycles.push(cycle),
+            Err(entry) => errors.push(format!("entry {}: no cycle in {}", i + 1, entry)),
+        }
+    }
+    (cycles, errors)
+}
+
+pub(crate) fn minimize_cycles(options: super::MinimizeCyclesOptions) -> Result<(), Box<dyn Error>> {
+    let cycles_results_file = options.cycle_results_file;
+    let files = super::ruff_util::ruff_graph(false, Direction::Dependencies, None);
+    let graph = to_module_graph(&files);
+    let module_files = files
+        .keys()
+        .map(|f| (path_to_module(f), f.as_str()))
+        .collect::<HashMap<_, _>>();
+    let mut locator = ImportLocator::default();
+
+    let contents = fs::read_to_string(&cycles_results_file)
+        .map_err(|e| format!("couldn't read {}: {}", cycles_results_file, e))?;
+    let (cycles, errors) = parse_cycles(&contents);
+    for error in &errors {
+        eprintln!("{}: {}", cycles_results_file, error);
+    }
+    let mut cycles = cycles
+        .iter()
+        .map(|cycle| cycle.iter().map(String::as_str).collect())
         .collect::<Vec<Vec<&str>>>();
 
     // sort cycles by length, since larger cycles are likelier to be minimized, and this
     // makes it easier to grok the results and logs
     cycles.sort_by_key(|a| a.len());
 
+    // minimizing a cycle ruff doesn't see would give a meaningless result
+    let (confirmed, false_positives): (Vec<_>, Vec<_>) = cycles
+        .iter()
+        .map(|cycle| (cycle, missing_edges(&graph, cycle)))
+        .partition(|(_, missing)| missing.is_empty());
+
     let mut minimal_cycles = Vec::<Vec<&str>>::new();
-    for cycle in &cycles {
-        minimal_cycles.push(minimize_cycle(&graph, cycle));
+    for (cycle, _) in &confirmed {
+        if options.all_minimal {
+            minimal_cycles.extend(minimal_sub_cycles(&graph, cycle));
+        } else {
+            minimal_cycles.push(minimize_cycle(&graph, cycle));
+        }
     }
 
     // find number of unique cycles, total length of all cycles
     let unique_minimal_cycles = minimal_cycles.iter().collect::<HashSet<_>>();
     for cycle in &unique_minimal_cycles {
         println!("{}", cycle.join(" -> "));
+        let cycle_files = cycle
+            .iter()
+            .map(|module| module_files.get(*module).copied())
+            .collect::<Option<Vec<_>>>();
+        for import in cycle_files
+            .map(|files| locator.describe_cycle(&files))
+            .unwrap_or_default()
+        {
+            println!("    {}", import);
+        }
     }
 
     println!();
@@ -114,7 +311,7 @@ pub(crate) fn minimize_cycles(cycles_results_file: String) {
     );
     println!(
         "longest cycle     : {}",
-        cycles.iter().map(|c| c.len()).max().unwrap()
+        cycles.iter().map(|c| c.len()).max().unwrap_or(0)
     );
     println!();
     println!("Post-minimization");
@@ -126,14 +323,138 @@ pub(crate) fn minimize_cycles(cycles_results_file: String) {
 
     println!(
         "longest cycle     : {}",
-        unique_minimal_cycles.iter().map(|c| c.len()).max().unwrap()
+        unique_minimal_cycles
+            .iter()
+            .map(|c| c.len())
+            .max()
+            .unwrap_or(0)
     );
+
+    println!();
+    println!("Validation against ruff's graph");
+    println!("confirmed cycles  : {}", confirmed.len());
+    println!("false positives   : {}", false_positives.len());
+    let disagreeing = false_positives
+        .iter()
+        .flat_map(|(_, missing)| missing)
+        .collect::<BTreeSet<_>>();
+    println!("disagreeing edges : {}", disagreeing.len());
+    for (cycle, missing) in &false_positives {
+        println!("{}", cycle.join(" -> "));
+        for (from, to) in missing {
+            let unknown = [from, to]
+                .into_iter()
+                .filter(|module| !graph.contains_key(**module))
+                .map(|module| format!("{} isn't in ruff's graph", module))
+                .collect::<Vec<_>>();
+            match unknown.is_empty() {
+                true => println!("    no import {} -> {}", from, to),
+                false => println!("    no import {} -> {} ({})", from, to, unknown.join(", ")),
+            }
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn cycles(contents: &str) -> (Vec<String>, Vec<String>) {
+        let (cycles, errors) = parse_cycles(contents);
+        (cycles.iter().map(|c| c.join(" -> ")).collect(), errors)
+    }
+
+    #[test]
+    fn test_parse_pylint_text() {
+        let contents = "\
+************* Module pkg.a
+src/pkg/a.py:1:0: R0401: Cyclic import (pkg.a -> pkg.b) (cyclic-import)
+src/pkg/a.py:1:0: R0401: Cyclic import (pkg.a -> pkg.b -> pkg.c) (cyclic-import)
+src/pkg/a.py:1:0: R0401: Cyclic import -> (cyclic-import)
+
+------------------------------------------------------------------
+Your code has been rated at 9.99/10
+";
+        assert_eq!(
+            cycles(contents),
+            (
+                vec!["pkg.a -> pkg.b".to_string(), "pkg.a -> pkg.b -> pkg.c".to_string()],
+                vec!["line 4: no cycle in pylint message 'src/pkg/a.py:1:0: R0401: Cyclic import -> (cyclic-import)'".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_plain_cycles() {
+        let contents = "a -> b -> c\nsrc/pkg/a.py -> src/pkg/b.py -> src/pkg/a.py\n\na ->\n";
+        assert_eq!(
+            cycles(contents),
+            (
+                vec!["a -> b -> c".to_string(), "pkg.a -> pkg.b".to_string()],
+                vec!["line 4: expected a cycle like 'a -> b -> c', got 'a ->'".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_pylint_json() {
+        let json = r#"[
+            {"symbol": "cyclic-import", "message-id": "R0401", "message": "Cyclic import (a -> b)"},
+            {"symbol": "unused-import", "message-id": "W0611", "message": "Unused import os"},
+            {"symbol": "cyclic-import", "message-id": "R0401"}
+        ]"#;
+        let (parsed, errors) = cycles(json);
+        assert_eq!(parsed, ["a -> b"]);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("entry 3: no cycle in "));
+
+        let json2 = r#"{
+            "messages": [{"symbol": "cyclic-import", "messageId": "R0401", "message": "Cyclic import (a -> b -> c)"}],
+            "statistics": {}
+        }"#;
+        assert_eq!(cycles(json2), (vec!["a -> b -> c".to_string()], vec![]));
+    }
+
+    #[test]
+    fn test_parse_sarif() {
+        let mut log = super::super::sarif::log(
+            &[super::super::sarif::CYCLIC_IMPORT],
+            vec![
+                serde_json::json!({
+                    "ruleId": "cyclic-import",
+                    "message": {"text": "Import cycle: src/a.py -> src/b.py -> src/a.py"},
+                }),
+                serde_json::json!({
+                    "ruleId": "cyclic-import",
+                    "message": {"text": "Import cycle: src/a.py -> src/b.py -> src/a.py"},
+                    "partialFingerprints": {"importCycle/v1": "src/c.py -> src/d.py"},
+                }),
+                serde_json::json!({"ruleId": "unused-cycle-suppression"}),
+            ],
+        );
+        assert_eq!(
+            cycles(&log.to_string()),
+            (vec!["a -> b".to_string(), "c -> d".to_string()], vec![])
+        );
+        // entries are numbered among all results, not just the cycles
+        log["runs"][0]["results"]
+            .as_array_mut()
+            .unwrap()
+            .push(serde_json::json!({"ruleId": "cyclic-import"}));
+        assert!(cycles(&log.to_string()).1[0].starts_with("entry 4: no cycle in "));
+
+        log["runs"] = serde_json::json!(null);
+        assert_eq!(
+            cycles(&log.to_string()).1,
+            ["expected pylint's JSON output or a SARIF log"]
+        );
+        assert_eq!(
+            cycles("[\n{,\n]").1,
+            ["line 2: invalid JSON: key must be a string at line 2 column 2"]
+        );
+    }
+
     #[test]
     fn test_cycle_size() {
         // unchanged cycle
@@ -244,4 +565,76 @@ mod tests {
             ["j", "l", "l1"]
         );
     }
+
+    #[test]
+    fn test_missing_edges() {
+        let graph = HashMap::from([
+            ("a".to_string(), HashSet::from(["b".to_string()])),
+            ("b".to_string(), HashSet::from(["c".to_string()])),
+            ("c".to_string(), HashSet::new()),
+        ]);
+        assert_eq!(missing_edges(&graph, &["a", "b"]), [("b", "a")]);
+        assert_eq!(missing_edges(&graph, &["a", "b", "c"]), [("c", "a")]);
+        assert_eq!(missing_edges(&graph, &["b", "x"]), [("b", "x"), ("x", "b")]);
+    }
+
+    /// the best chord leaves a cycle that still has chords, which get applied in turn
+    #[test]
+    fn test_minimize_cycle_multi_step() {
+        let graph = HashMap::from([
+            ("a".to_string(), HashSet::from(["e".to_string()])),
+            ("f".to_string(), HashSet::from(["h".to_string()])),
+        ]);
+        // a -> e (cuts 3), then f -> h (cuts 1)
+        assert_eq!(
+            minimize_cycle(&graph, &["a", "b", "c", "d", "e", "f", "g", "h"]),
+            ["a", "e", "f", "h"]
+        );
+
+        let graph = HashMap::from([
+            ("b".to_string(), HashSet::from(["d".to_string()])),
+            ("f".to_string(), HashSet::from(["b".to_string()])),
+        ]);
+        // shortcut b -> d (cuts 1), then the contained cycle b -> d -> e -> f
+        assert_eq!(
+            minimize_cycle(&graph, &["a", "b", "c", "d", "e", "f"]),
+            ["b", "d", "e", "f"]
+        );
+
+        // the import closing the cycle isn't a chord
+        let graph = HashMap::from([("c".to_string(), HashSet::from(["a".to_string()]))]);
+        assert_eq!(minimize_cycle(&graph, &["a", "b", "c"]), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_minimal_sub_cycles() {
+        let graph = HashMap::from([
+            ("a".to_string(), HashSet::from(["c".to_string()])),
+            ("b".to_string(), HashSet::from(["d".to_string()])),
+        ]);
+        // either chord gives a chordless cycle
+        assert_eq!(
+            minimal_sub_cycles(&graph, &["a", "b", "c", "d"]),
+            BTreeSet::from([vec!["a", "b", "d"], vec!["a", "c", "d"]])
+        );
+        assert_eq!(
+            minimize_cycle(&graph, &["a", "b", "c", "d"]),
+            ["a", "c", "d"]
+        );
+
+        let graph = HashMap::from([
+            ("a".to_string(), HashSet::from(["e".to_string()])),
+            ("f".to_string(), HashSet::from(["h".to_string()])),
+        ]);
+        // both chords have to be applied, in whichever order
+        assert_eq!(
+            minimal_sub_cycles(&graph, &["a", "b", "c", "d", "e", "f", "g", "h"]),
+            BTreeSet::from([vec!["a", "e", "f", "h"]])
+        );
+        // already chordless
+        assert_eq!(
+            minimal_sub_cycles(&graph, &["b", "c", "a"]),
+            BTreeSet::from([vec!["a", "b", "c"]])
+        );
+    }
 }
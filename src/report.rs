@@ -0,0 +1,123 @@
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+
+use super::cycle_detection::{detect_cycles_in_graph, edge_frequencies};
+use super::ruff_util::{ruff_graph, Direction};
+
+const TEMPLATE: &str = include_str!("report.html");
+
+/// Everything the report shows, as the JSON that gets embedded in the page
+pub(crate) fn report_data(graph: &HashMap<String, HashSet<String>>) -> Value {
+    let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (module, dependencies) in graph.iter() {
+        for dependency in dependencies.iter() {
+            dependents.entry(dependency).or_default().push(module);
+        }
+    }
+
+    // modules that are only ever imported (e.g. third-party ones) don't have an entry
+    let mut modules: BTreeMap<&str, (Vec<&str>, Vec<&str>)> = BTreeMap::new();
+    for module in graph.keys().chain(graph.values().flatten()) {
+        modules.entry(module).or_insert_with(|| {
+            let mut module_dependencies = graph
+                .get(module)
+                .map(|d| d.iter().map(String::as_str).collect::<Vec<_>>())
+                .unwrap_or_default();
+            module_dependencies.sort();
+            let mut module_dependents =
+                dependents.get(module.as_str()).cloned().unwrap_or_default();
+            module_dependents.sort();
+            (module_dependencies, module_dependents)
+        });
+    }
+
+    let mut cycles = detect_cycles_in_graph(graph)
+        .into_iter()
+        .collect::<Vec<_>>();
+    cycles.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+    let mut edges = edge_frequencies(&cycles.iter().cloned().collect())
+        .into_iter()
+        .collect::<Vec<_>>();
+    edges.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    json!({
+        "modules": modules
+            .into_iter()
+            .map(|(name, (dependencies, dependents))| json!({
+                "name": name,
+                "fan_in": dependents.len(),
+                "fan_out": dependencies.len(),
+                "dependencies": dependencies,
+                "dependents": dependents,
+            }))
+            .collect::<Vec<_>>(),
+        "cycles": cycles,
+        "edges": edges
+            .into_iter()
+            .map(|((from, to), frequency)| json!({"from": from, "to": to, "cycles": frequency}))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// A single HTML file with the data and all scripts and styles inline, so it works
+/// offline and can be published as a CI artifact
+pub(crate) fn render_html(data: &Value) -> String {
+    // `</script>` in a module name would otherwise end the data block early
+    TEMPLATE.replace("{{DATA}}", &data.to_string().replace("</", "<\\/"))
+}
+
+pub(crate) fn report(options: super::ReportOptions) -> Result<(), Box<dyn Error>> {
+    let graph = ruff_graph(true, Direction::Dependencies, None);
+    fs::write(&options.html, render_html(&report_data(&graph)))
+        .map_err(|e| format!("couldn't write {}: {}", options.html, e))?;
+    eprintln!("Wrote report to {}", options.html);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_data() {
+        let graph = HashMap::from([
+            ("a".to_string(), HashSet::from(["b".to_string()])),
+            (
+                "b".to_string(),
+                HashSet::from(["c".to_string(), "d".to_string(), "os".to_string()]),
+            ),
+            ("c".to_string(), HashSet::from(["a".to_string()])),
+            ("d".to_string(), HashSet::from(["a".to_string()])),
+        ]);
+        let data = report_data(&graph);
+
+        assert_eq!(
+            data["modules"][1],
+            json!({
+                "name": "b",
+                "fan_in": 1,
+                "fan_out": 3,
+                "dependencies": ["c", "d", "os"],
+                "dependents": ["a"],
+            })
+        );
+        assert_eq!(data["modules"][4]["name"], "os");
+        assert_eq!(data["cycles"], json!([["a", "b", "c"], ["a", "b", "d"]]));
+        assert_eq!(
+            data["edges"][0],
+            json!({"from": "a", "to": "b", "cycles": 2})
+        );
+        assert_eq!(data["edges"].as_array().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_render_html() {
+        let html = render_html(&json!({"modules": [{"name": "</script><script>"}]}));
+        assert!(html.contains(r#"{"modules":[{"name":"<\/script><script>"}]}"#));
+        assert!(!html.contains("{{DATA}}"));
+        // nothing is loaded from elsewhere
+        assert!(!html.contains("src=\"http"));
+    }
+}
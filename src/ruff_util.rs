@@ -1,23 +1,63 @@
-use regex::Regex;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::path::Path;
 use std::process::Command;
 use std::str;
+use std::sync::OnceLock;
 
 use serde_json::Value;
 
+/// The `ruff` to run, and the source roots modules are named relative to; set once from
+/// the config at startup
+static RUFF: OnceLock<String> = OnceLock::new();
+static SOURCE_ROOTS: OnceLock<Vec<String>> = OnceLock::new();
+
+pub(crate) fn configure(ruff: Option<String>, source_roots: Option<Vec<String>>) {
+    if let Some(ruff) = ruff {
+        RUFF.set(ruff).unwrap();
+    }
+    if let Some(source_roots) = source_roots {
+        SOURCE_ROOTS.set(source_roots).unwrap();
+    }
+}
+
+/// Which way the edges of the graph returned by `ruff_graph` point
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    /// each file maps to the files it imports; ruff's default
+    Dependencies,
+    /// each file maps to the files that import it
+    Dependents,
+}
+
+impl Direction {
+    fn ruff_args(self) -> &'static [&'static str] {
+        match self {
+            Direction::Dependencies => &[],
+            Direction::Dependents => &["--direction", "dependents"],
+        }
+    }
+}
+
 pub(crate) fn ruff_graph(
     as_pkgs: bool,
-    as_dependents: bool,
+    direction: Direction,
+    paths: Option<Vec<String>>,
+) -> HashMap<String, HashSet<String>> {
+    ruff_graph_in(Path::new("."), as_pkgs, direction, paths)
+}
+
+/// `ruff_graph` for the project in `dir`, e.g. a checkout of another revision
+pub(crate) fn ruff_graph_in(
+    dir: &Path,
+    as_pkgs: bool,
+    direction: Direction,
     paths: Option<Vec<String>>,
 ) -> HashMap<String, HashSet<String>> {
-    let graph_output = Command::new("ruff")
+    let graph_output = Command::new(RUFF.get().map_or("ruff", String::as_str))
+        .current_dir(dir)
         .args(["analyze", "graph", "--preview"])
-        .args(if as_dependents {
-            Vec::<&str>::new()
-        } else {
-            vec!["--direction", "dependents"]
-        })
+        .args(direction.ruff_args())
         .args(paths.unwrap_or_default())
         .output()
         .expect("failed to execute process");
@@ -55,20 +95,53 @@ pub(crate) fn ruff_graph(
         .collect::<HashMap<_, _>>()
 }
 
-fn path_to_module(path: &str) -> String {
+/// Turns a graph of files, as returned by `ruff_graph(false, ...)`, into one of modules
+pub(crate) fn to_module_graph(
+    graph: &HashMap<String, HashSet<String>>,
+) -> HashMap<String, HashSet<String>> {
+    graph
+        .iter()
+        .map(|(file, files)| {
+            (
+                path_to_module(file),
+                files.iter().map(|f| path_to_module(f)).collect(),
+            )
+        })
+        .collect()
+}
+
+pub(crate) fn path_to_module(path: &str) -> String {
+    path_to_module_in(path, SOURCE_ROOTS.get().map_or(&[], Vec::as_slice))
+}
+
+/// `path_to_module`, with modules named relative to the longest of `source_roots` the
+/// path is in, if any; an empty root is the current directory, which every path is in
+fn path_to_module_in(path: &str, source_roots: &[String]) -> String {
+    let in_root = |root: &str| match root {
+        "" => Some(path),
+        root => path.strip_prefix(root)?.strip_prefix('/'),
+    };
+    let rest = source_roots
+        .iter()
+        .map(|root| root.trim_end_matches('/'))
+        .filter_map(|root| Some((root.len(), in_root(root)?)))
+        .max_by_key(|(len, _)| *len);
+    if let Some((_, rest)) = rest {
+        let module = rest.replace("/__init__.py", "").replace("/", ".");
+        return module.strip_suffix(".py").unwrap_or(&module).to_string();
+    }
     // this by no means fully PEP-compliant, and does not work for flat layouts or custom
     // package organization; it works for default src-layouts only
-    let _module_path_with_extensions = path.replace("/__init__.py", "").replace("/", ".");
-    let full_module_path: String = Regex::new(r"\.py$")
-        .unwrap()
-        .replace(&_module_path_with_extensions, "")
-        .into();
+    let module_path_with_extension = path.replace("/__init__.py", "").replace("/", ".");
+    let full_module_path = module_path_with_extension
+        .strip_suffix(".py")
+        .unwrap_or(&module_path_with_extension);
     match full_module_path.find("src.") {
         Some(src_index) => {
             let start_index = src_index + 4; // "src.".len()
             full_module_path[start_index..].to_string()
         }
-        None => full_module_path,
+        None => full_module_path.to_string(),
     }
 }
 
@@ -76,10 +149,35 @@ fn path_to_module(path: &str) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_direction_ruff_args() {
+        assert!(Direction::Dependencies.ruff_args().is_empty());
+        assert_eq!(
+            Direction::Dependents.ruff_args(),
+            ["--direction", "dependents"]
+        );
+    }
+
     #[test]
     fn test_path_to_module() {
         assert_eq!(path_to_module("foo/src/foo/bar.py"), "foo.bar");
         assert_eq!(path_to_module("foo/src/foo/bar/__init__.py"), "foo.bar");
         assert_eq!(path_to_module("foo/src/foo/__init__.py"), "foo");
     }
+
+    #[test]
+    fn test_path_to_module_in_source_roots() {
+        let roots = ["lib".to_string(), "lib/vendored/".to_string()];
+        assert_eq!(path_to_module_in("lib/foo/bar.py", &roots), "foo.bar");
+        assert_eq!(
+            path_to_module_in("lib/vendored/baz/__init__.py", &roots),
+            "baz"
+        );
+        assert_eq!(path_to_module_in("library/foo.py", &roots), "library.foo");
+        assert_eq!(path_to_module_in("foo/src/foo/bar.py", &roots), "foo.bar");
+
+        let roots = ["".to_string(), "lib".to_string()];
+        assert_eq!(path_to_module_in("pkg/foo.py", &roots), "pkg.foo");
+        assert_eq!(path_to_module_in("lib/foo.py", &roots), "foo");
+    }
 }
@@ -0,0 +1,57 @@
+use serde_json::{json, Value};
+
+/// A kind of problem reported in SARIF logs, which code scanning tools show with results
+pub(crate) struct Rule {
+    pub(crate) id: &'static str,
+    pub(crate) name: &'static str,
+    pub(crate) description: &'static str,
+}
+
+pub(crate) const CYCLIC_IMPORT: Rule = Rule {
+    id: "cyclic-import",
+    name: "CyclicImport",
+    description: "Modules import each other, directly or indirectly",
+};
+
+pub(crate) const UNUSED_SUPPRESSION: Rule = Rule {
+    id: "unused-cycle-suppression",
+    name: "UnusedCycleSuppression",
+    description: "An import is marked to be ignored in cycles, but isn't part of any",
+};
+
+/// A place in a file; SARIF consumers such as GitHub code scanning need a line to show
+/// results inline
+pub(crate) fn location(uri: &str, line: usize) -> Value {
+    json!({
+        "physicalLocation": {
+            "artifactLocation": {"uri": uri},
+            "region": {"startLine": line},
+        }
+    })
+}
+
+/// A SARIF 2.1.0 log with a single run of ruff-tools
+pub(crate) fn log(rules: &[Rule], results: Vec<Value>) -> Value {
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "ruff-tools",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_HOMEPAGE"),
+                    "rules": rules
+                        .iter()
+                        .map(|rule| json!({
+                            "id": rule.id,
+                            "name": rule.name,
+                            "shortDescription": {"text": rule.description},
+                        }))
+                        .collect::<Vec<_>>(),
+                }
+            },
+            "results": results,
+        }]
+    })
+}
@@ -0,0 +1,142 @@
+use regex::Regex;
+use std::collections::HashSet;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Which of the transitively affected files get passed to the command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    All,
+    Tests,
+}
+
+impl FromStr for Selection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Selection::All),
+            "tests" => Ok(Selection::Tests),
+            _ => Err(format!(
+                "unknown selection '{}', expected 'all' or 'tests'",
+                s
+            )),
+        }
+    }
+}
+
+/// Recognises test files the same way pytest's `python_files` option does: by matching
+/// glob patterns against the file's basename
+pub(crate) struct TestMatcher {
+    patterns: Vec<Regex>,
+}
+
+impl TestMatcher {
+    /// `patterns` is a comma- or whitespace-separated list of globs, such as pytest's
+    /// default of `test_*.py *_test.py`
+    pub(crate) fn new(patterns: &str) -> Self {
+        TestMatcher {
+            patterns: patterns
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|p| !p.is_empty())
+                .map(glob_to_regex)
+                .collect(),
+        }
+    }
+
+    pub(crate) fn is_test_file(&self, path: &str) -> bool {
+        let file_name = basename(path);
+        self.patterns.iter().any(|p| p.is_match(file_name))
+    }
+
+    /// `conftest.py` files aren't imported by the tests they configure, so ruff's graph
+    /// never lists tests as their dependents; instead, every test file in or below the
+    /// conftest's directory is treated as one
+    pub(crate) fn conftest_dependents<'a>(
+        &self,
+        conftest: &str,
+        files: impl Iterator<Item = &'a String>,
+    ) -> HashSet<String> {
+        let conftest_dir = Path::new(conftest).parent().unwrap_or(Path::new(""));
+        files
+            .filter(|f| Path::new(f).starts_with(conftest_dir) && self.is_test_file(f))
+            .cloned()
+            .collect()
+    }
+}
+
+pub(crate) fn is_conftest(path: &str) -> bool {
+    basename(path) == "conftest.py"
+}
+
+fn basename(path: &str) -> &str {
+    Path::new(path)
+        .file_name()
+        .and_then(|f| f.to_str())
+        .unwrap_or(path)
+}
+
+/// Translates an fnmatch-style glob (`*`, `?`) into an anchored regex
+fn glob_to_regex(glob: &str) -> Regex {
+    let mut pattern = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            _ => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_test_file() {
+        let matcher = TestMatcher::new("test_*.py,*_test.py");
+        assert!(matcher.is_test_file("src/util/tests/test_foo.py"));
+        assert!(matcher.is_test_file("src/util/foo_test.py"));
+        assert!(matcher.is_test_file("test_foo.py"));
+        assert!(!matcher.is_test_file("src/util/foo.py"));
+        assert!(!matcher.is_test_file("src/test_util/foo.py"));
+        assert!(!matcher.is_test_file("src/util/test_foo.pyi"));
+
+        // pytest-style whitespace separation
+        let matcher = TestMatcher::new("check_?.py  *_spec.py");
+        assert!(matcher.is_test_file("src/check_a.py"));
+        assert!(!matcher.is_test_file("src/check_ab.py"));
+        assert!(matcher.is_test_file("src/foo_spec.py"));
+    }
+
+    #[test]
+    fn test_conftest_dependents() {
+        let matcher = TestMatcher::new("test_*.py");
+        let files = [
+            "src/util/conftest.py".to_string(),
+            "src/util/test_bar.py".to_string(),
+            "src/util/tests/test_foo.py".to_string(),
+            "src/util/bar.py".to_string(),
+            "src/other/test_baz.py".to_string(),
+            "src/utility/test_qux.py".to_string(),
+        ];
+        assert_eq!(
+            matcher.conftest_dependents("src/util/conftest.py", files.iter()),
+            HashSet::from([
+                "src/util/test_bar.py".to_string(),
+                "src/util/tests/test_foo.py".to_string(),
+            ])
+        );
+        // a root conftest applies to everything
+        assert_eq!(
+            matcher
+                .conftest_dependents("conftest.py", files.iter())
+                .len(),
+            4
+        );
+        assert!(is_conftest("src/util/conftest.py"));
+        assert!(!is_conftest("src/util/not_conftest.py"));
+    }
+}
@@ -0,0 +1,204 @@
+use serde_json::json;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use super::ruff_util::{path_to_module, ruff_graph, Direction};
+use super::test_selection::{is_conftest, TestMatcher};
+
+/// Modules of the console scripts in a `pyproject.toml`, e.g. `pkg.cli` for
+/// `cli = "pkg.cli:main"`, from PEP 621 and Poetry script tables
+pub(crate) fn script_modules(pyproject: &str) -> Result<Vec<String>, toml::de::Error> {
+    let pyproject = pyproject.parse::<toml::Table>()?;
+    let tables = [
+        pyproject.get("project").and_then(|p| p.get("scripts")),
+        pyproject.get("project").and_then(|p| p.get("gui-scripts")),
+        pyproject
+            .get("tool")
+            .and_then(|t| t.get("poetry"))
+            .and_then(|p| p.get("scripts")),
+    ];
+    Ok(tables
+        .into_iter()
+        .flatten()
+        .filter_map(|table| table.as_table())
+        .flat_map(|table| table.values())
+        // poetry also allows `{ reference = ..., type = "file" }`, which isn't a module
+        .filter_map(|target| target.as_str())
+        .map(|target| target.split(':').next().unwrap().trim().to_string())
+        .collect())
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Unreachable {
+    /// first-party files no entrypoint imports, directly or indirectly
+    unreachable: BTreeSet<String>,
+    /// first-party files nothing imports, that aren't entrypoints themselves
+    no_dependents: BTreeSet<String>,
+}
+
+/// Whether a file is run rather than imported: an explicit entrypoint, a test, a conftest
+/// or a `__main__.py`
+fn is_entrypoint(file: &str, entry_modules: &HashSet<String>, test_matcher: &TestMatcher) -> bool {
+    entry_modules.contains(&path_to_module(file))
+        || test_matcher.is_test_file(file)
+        || is_conftest(file)
+        || file.ends_with("__main__.py")
+}
+
+pub(crate) fn find_unreachable(
+    graph: &HashMap<String, HashSet<String>>,
+    entry_modules: &HashSet<String>,
+    test_matcher: &TestMatcher,
+) -> Unreachable {
+    let entrypoints = graph
+        .keys()
+        .filter(|file| is_entrypoint(file, entry_modules, test_matcher))
+        .collect::<Vec<_>>();
+
+    let mut reached = entrypoints.iter().copied().collect::<HashSet<_>>();
+    let mut queue = entrypoints.iter().copied().collect::<VecDeque<_>>();
+    while let Some(file) = queue.pop_front() {
+        // importing a module also runs the `__init__.py` of every package it's in,
+        // which ruff doesn't list as imports
+        let packages = Path::new(file)
+            .ancestors()
+            .skip(1)
+            .filter_map(|dir| graph.get_key_value(dir.join("__init__.py").to_str()?))
+            .map(|(init, _)| init);
+        for dependency in graph.get(file).into_iter().flatten().chain(packages) {
+            if reached.insert(dependency) {
+                queue.push_back(dependency);
+            }
+        }
+    }
+
+    let imported = graph.values().flatten().collect::<HashSet<_>>();
+    Unreachable {
+        unreachable: graph
+            .keys()
+            .filter(|file| !reached.contains(file))
+            .cloned()
+            .collect(),
+        no_dependents: graph
+            .keys()
+            .filter(|file| {
+                !imported.contains(file)
+                    && !file.ends_with("__init__.py")
+                    && !is_entrypoint(file, entry_modules, test_matcher)
+            })
+            .cloned()
+            .collect(),
+    }
+}
+
+pub(crate) fn unreachable(options: super::UnreachableOptions) -> Result<(), Box<dyn Error>> {
+    let mut entry_modules = options.entry.iter().cloned().collect::<HashSet<_>>();
+    if Path::new(&options.pyproject).exists() {
+        let pyproject = fs::read_to_string(&options.pyproject)?;
+        let scripts = script_modules(&pyproject)
+            .map_err(|e| format!("couldn't parse {}: {}", options.pyproject, e))?;
+        entry_modules.extend(scripts);
+    }
+    let test_matcher = TestMatcher::new(&options.test_patterns);
+
+    let graph = ruff_graph(false, Direction::Dependencies, None);
+    if !graph
+        .keys()
+        .any(|file| is_entrypoint(file, &entry_modules, &test_matcher))
+    {
+        eprintln!("No entrypoints found; pass some with --entry");
+    }
+    let result = find_unreachable(&graph, &entry_modules, &test_matcher);
+
+    if options.json {
+        let result = json!({
+            "unreachable": result.unreachable,
+            "no_dependents": result.no_dependents,
+        });
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
+    }
+    println!("Unreachable from any entrypoint:");
+    for file in result.unreachable.iter() {
+        println!("  {}", file);
+    }
+    println!();
+    println!("Not imported anywhere:");
+    for file in result.no_dependents.iter() {
+        println!("  {}", file);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_modules() {
+        let pyproject = r#"
+[project]
+name = "pkg"
+
+[project.scripts]
+pkg = "pkg.cli:main"
+pkg-admin = "pkg.admin.__main__ : run"
+
+[project.gui-scripts]
+pkg-gui = "pkg.gui:main"
+
+[tool.poetry.scripts]
+legacy = "pkg.legacy:main"
+installer = { reference = "install.sh", type = "file" }
+"#;
+        assert_eq!(
+            script_modules(pyproject).unwrap(),
+            ["pkg.cli", "pkg.admin.__main__", "pkg.gui", "pkg.legacy"]
+        );
+        assert!(script_modules("[project").is_err());
+        assert!(script_modules("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_find_unreachable() {
+        let graph = HashMap::from([
+            ("src/pkg/__init__.py".to_string(), HashSet::new()),
+            (
+                "src/pkg/cli.py".to_string(),
+                HashSet::from(["src/pkg/core.py".to_string()]),
+            ),
+            ("src/pkg/core.py".to_string(), HashSet::new()),
+            (
+                "src/pkg/dead.py".to_string(),
+                HashSet::from(["src/pkg/dead_helper.py".to_string()]),
+            ),
+            ("src/pkg/dead_helper.py".to_string(), HashSet::new()),
+            (
+                "src/pkg/tested_only.py".to_string(),
+                HashSet::from(["src/pkg/sub/__init__.py".to_string()]),
+            ),
+            ("src/pkg/sub/__init__.py".to_string(), HashSet::new()),
+            (
+                "tests/test_tested_only.py".to_string(),
+                HashSet::from(["src/pkg/tested_only.py".to_string()]),
+            ),
+        ]);
+        let result = find_unreachable(
+            &graph,
+            &HashSet::from(["pkg.cli".to_string()]),
+            &TestMatcher::new("test_*.py"),
+        );
+        assert_eq!(
+            result,
+            Unreachable {
+                unreachable: BTreeSet::from([
+                    "src/pkg/dead.py".to_string(),
+                    "src/pkg/dead_helper.py".to_string(),
+                ]),
+                no_dependents: BTreeSet::from(["src/pkg/dead.py".to_string()]),
+            }
+        );
+    }
+}
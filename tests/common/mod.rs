@@ -0,0 +1,196 @@
+//! Helpers for running `ruff-tools` against a fake `ruff` binary, which serves a fixed
+//! import graph and logs the arguments of every invocation
+#![allow(dead_code)]
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+pub struct FakeRuff {
+    root: PathBuf,
+}
+
+impl FakeRuff {
+    /// `dependencies` maps each file to the files it imports, like `ruff analyze graph`
+    pub fn new(dependencies: &[(&str, &[&str])]) -> Self {
+        let root = std::env::temp_dir().join(format!(
+            "ruff-tools-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::create_dir_all(root.join("project")).unwrap();
+        let ruff = FakeRuff { root };
+        ruff.set_graph(dependencies);
+
+        let script = format!(
+            r#"#!/bin/sh
+echo "$@" >> "{root}/invocations.log"
+if [ -f ruff-graph.json ]; then cat ruff-graph.json; exit; fi
+case " $* " in
+  *" --direction dependents "*) cat "{root}/dependents.json" ;;
+  *) cat "{root}/dependencies.json" ;;
+esac
+"#,
+            root = ruff.root.display()
+        );
+        let bin = ruff.root.join("bin").join("ruff");
+        fs::write(&bin, script).unwrap();
+        fs::set_permissions(&bin, fs::Permissions::from_mode(0o755)).unwrap();
+
+        ruff
+    }
+
+    /// Replaces the graph served from now on; the whole graph is always served, even
+    /// when `ruff` is asked about specific files, unless it's run from a directory with
+    /// a `ruff-graph.json`, e.g. a checkout of another revision
+    pub fn set_graph(&self, dependencies: &[(&str, &[&str])]) {
+        let root = &self.root;
+
+        let mut dependents: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for (file, imports) in dependencies {
+            dependents.entry(file).or_default();
+            for import in imports.iter() {
+                dependents.entry(import).or_default().insert(file);
+            }
+        }
+        let dependencies: BTreeMap<&str, BTreeSet<&str>> = dependencies
+            .iter()
+            .map(|(file, imports)| (*file, imports.iter().copied().collect()))
+            .collect();
+        fs::write(
+            root.join("dependencies.json"),
+            serde_json::to_string(&dependencies).unwrap(),
+        )
+        .unwrap();
+        fs::write(
+            root.join("dependents.json"),
+            serde_json::to_string(&dependents).unwrap(),
+        )
+        .unwrap();
+    }
+
+    /// The directory `ruff-tools` is run from
+    pub fn project_dir(&self) -> PathBuf {
+        self.root.join("project")
+    }
+
+    pub fn command(&self, args: &[&str]) -> Command {
+        let path = format!(
+            "{}:{}",
+            self.root.join("bin").display(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+        let mut command = Command::new(env!("CARGO_BIN_EXE_ruff-tools"));
+        command
+            .args(args)
+            .env("PATH", path)
+            .current_dir(self.project_dir());
+        command
+    }
+
+    /// The arguments of every `ruff` invocation so far, in order
+    pub fn invocations(&self) -> Vec<String> {
+        fs::read_to_string(self.root.join("invocations.log"))
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    pub fn write_file(&self, path: impl AsRef<Path>, contents: &str) -> PathBuf {
+        let path = self.project_dir().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+}
+
+impl Drop for FakeRuff {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// A running `ruff-tools live`, killed when dropped
+pub struct LiveProcess {
+    child: Child,
+    lines: mpsc::Receiver<String>,
+}
+
+impl LiveProcess {
+    pub fn spawn(mut command: Command) -> Self {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (tx, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if tx.send(line.unwrap()).is_err() {
+                    break;
+                }
+            }
+        });
+        LiveProcess { child, lines }
+    }
+
+    /// Consumes output up to and including the first line containing `needle`, and
+    /// returns the consumed lines
+    pub fn wait_for(&self, needle: &str) -> Vec<String> {
+        let mut seen = Vec::new();
+        loop {
+            let line = self
+                .lines
+                .recv_timeout(Duration::from_secs(30))
+                .unwrap_or_else(|_| panic!("timed out waiting for '{}' in {:?}", needle, seen));
+            let found = line.contains(needle);
+            seen.push(line);
+            if found {
+                return seen;
+            }
+        }
+    }
+}
+
+impl LiveProcess {
+    /// Types a line on stdin
+    pub fn send(&mut self, line: &str) {
+        let stdin = self.child.stdin.as_mut().unwrap();
+        writeln!(stdin, "{}", line).unwrap();
+        stdin.flush().unwrap();
+    }
+
+    pub fn wait_for_exit(&mut self) -> ExitStatus {
+        let start = Instant::now();
+        loop {
+            if let Some(status) = self.child.try_wait().unwrap() {
+                return status;
+            }
+            assert!(
+                start.elapsed() < Duration::from_secs(30),
+                "timed out waiting for exit"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+impl Drop for LiveProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,162 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::FakeRuff;
+use serde_json::Value;
+
+const GRAPH: &[(&str, &[&str])] = &[
+    ("a.py", &["b.py"]),
+    ("b.py", &["a.py"]),
+    ("c.py", &["d.py"]),
+    ("d.py", &["c.py"]),
+];
+
+#[test]
+fn test_config_from_pyproject() {
+    let ruff = FakeRuff::new(GRAPH);
+    ruff.write_file(
+        "pyproject.toml",
+        r#"
+[project]
+name = "pkg"
+
+[tool.ruff-tools.detect-cycles]
+format = "sarif"
+exclude-edges = ["c -> d"]
+"#,
+    );
+    // run from a subdirectory, like ruff
+    std::fs::create_dir_all(ruff.project_dir().join("sub")).unwrap();
+    let output = ruff
+        .command(&["detect-cycles"])
+        .current_dir(ruff.project_dir().join("sub"))
+        .output()
+        .unwrap();
+    let log: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let results = log["runs"][0]["results"].as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(
+        results[0]["message"]["text"],
+        "Import cycle: a.py -> b.py -> a.py"
+    );
+
+    // flags take precedence
+    let output = ruff
+        .command(&[
+            "detect-cycles",
+            "--format",
+            "text",
+            "--exclude-edges",
+            "a.py -> b.py",
+        ])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("c.py -> d.py\n"), "{}", stdout);
+}
+
+#[test]
+fn test_config_live() {
+    let ruff = FakeRuff::new(GRAPH);
+    ruff.write_file(
+        "ruff-tools.toml",
+        "[live]\ncmd = [\"echo\", \"from config\"]\npaths = [\"a.py\"]\n",
+    );
+    let output = ruff
+        .command(&["live", "--once", "--run-on-start"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("from config a.py\n"), "{}", stdout);
+}
+
+#[test]
+fn test_config_live_paths_current_directory() {
+    let ruff = FakeRuff::new(GRAPH);
+    ruff.write_file(
+        "ruff-tools.toml",
+        "src = [\".\"]\n[live]\ncmd = [\"echo\"]\npaths = [\".\"]\n",
+    );
+    let output = ruff
+        .command(&["live", "--once", "--run-on-start"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let line = stdout
+        .lines()
+        .find_map(|l| l.strip_prefix("Transitively affected files: "))
+        .unwrap();
+    let mut files = line.split(", ").collect::<Vec<_>>();
+    files.sort();
+    assert_eq!(files, ["a.py", "b.py", "c.py", "d.py"]);
+}
+
+#[test]
+fn test_invalid_config() {
+    let ruff = FakeRuff::new(GRAPH);
+    ruff.write_file("ruff-tools.toml", "[detect-cycles]\nformat = \"xml\"\n");
+    let output = ruff.command(&["detect-cycles"]).output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("`detect-cycles.format` in ")
+            && stderr.contains("unknown format 'xml', expected 'text' or 'sarif'"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_baseline() {
+    let ruff = FakeRuff::new(GRAPH);
+    let output = ruff
+        .command(&[
+            "detect-cycles",
+            "--baseline",
+            "cycles.txt",
+            "--write-baseline",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let baseline = std::fs::read_to_string(ruff.project_dir().join("cycles.txt")).unwrap();
+    assert_eq!(baseline, "a.py -> b.py\nc.py -> d.py\n");
+
+    let output = ruff
+        .command(&["detect-cycles", "--baseline", "cycles.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // a new cycle, and one of the known ones is gone
+    ruff.set_graph(&[
+        ("a.py", &["b.py"]),
+        ("b.py", &["a.py"]),
+        ("c.py", &["d.py"]),
+        ("d.py", &[]),
+        ("e.py", &["f.py"]),
+        ("f.py", &["e.py"]),
+    ]);
+    let output = ruff
+        .command(&["detect-cycles", "--baseline", "cycles.txt"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stdout.starts_with("e.py -> f.py\n"), "{}", stdout);
+    assert!(stdout.contains("# in the baseline : 1\n"), "{}", stdout);
+    assert!(
+        stderr.contains("1 cycles in cycles.txt no longer exist"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("1 cycles aren't in the baseline cycles.txt"),
+        "{}",
+        stderr
+    );
+}
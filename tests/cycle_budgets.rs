@@ -0,0 +1,117 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::FakeRuff;
+
+/// two cycles, a -> b and c -> d -> e, with one more import in the second's SCC
+const GRAPH: &[(&str, &[&str])] = &[
+    ("a.py", &["b.py"]),
+    ("b.py", &["a.py"]),
+    ("c.py", &["d.py"]),
+    ("d.py", &["e.py"]),
+    ("e.py", &["c.py", "f.py"]),
+    ("f.py", &["e.py"]),
+];
+
+#[test]
+fn test_within_budget() {
+    let ruff = FakeRuff::new(GRAPH);
+    let output = ruff
+        .command(&[
+            "detect-cycles",
+            "--max-cycles",
+            "3",
+            "--max-cycle-length",
+            "3",
+            "--max-total-cycle-length",
+            "7",
+            "--max-scc-size",
+            "4",
+        ])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("largest SCC       : 4\n"), "{}", stdout);
+}
+
+#[test]
+fn test_over_budget() {
+    let ruff = FakeRuff::new(GRAPH);
+    let output = ruff
+        .command(&[
+            "detect-cycles",
+            "--format",
+            "sarif",
+            "--max-cycles",
+            "2",
+            "--max-cycle-length",
+            "2",
+            "--max-scc-size",
+            "3",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "\
+number of cycles is 3, over the limit of 2 (--max-cycles)
+longest cycle is 3, over the limit of 2 (--max-cycle-length)
+largest SCC is 4, over the limit of 3 (--max-scc-size)
+"
+    );
+}
+
+#[test]
+fn test_budget_from_config() {
+    let ruff = FakeRuff::new(GRAPH);
+    ruff.write_file(
+        "ruff-tools.toml",
+        "[detect-cycles]\nmax-total-cycle-length = 6\n",
+    );
+    let output = ruff.command(&["detect-cycles"]).output().unwrap();
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "total cycle length is 7, over the limit of 6 (--max-total-cycle-length)\n"
+    );
+
+    // flags take precedence
+    let output = ruff
+        .command(&["detect-cycles", "--max-total-cycle-length", "7"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_budget_counts_baseline() {
+    let ruff = FakeRuff::new(GRAPH);
+    ruff.write_file("cycles.txt", "a.py -> b.py\n");
+    let output = ruff
+        .command(&[
+            "detect-cycles",
+            "--baseline",
+            "cycles.txt",
+            "--max-cycles",
+            "2",
+            "--max-cycle-length",
+            "2",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("# cycles          : 3\n"), "{}", stdout);
+    assert!(stdout.contains("# in the baseline : 1\n"), "{}", stdout);
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "\
+2 cycles aren't in the baseline cycles.txt
+number of cycles is 3, over the limit of 2 (--max-cycles)
+longest cycle is 3, over the limit of 2 (--max-cycle-length)
+"
+    );
+}
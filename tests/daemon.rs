@@ -0,0 +1,90 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::{FakeRuff, LiveProcess};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const GRAPH: &[(&str, &[&str])] = &[
+    ("a.py", &["b.py"]),
+    ("b.py", &["c.py"]),
+    ("c.py", &["a.py"]),
+    ("tests/test_a.py", &["a.py"]),
+];
+
+fn query(ruff: &FakeRuff, args: &[&str]) -> (bool, String) {
+    let output = ruff.command(&[&["query"], args].concat()).output().unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap() + &String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn test_daemon_queries() {
+    let ruff = FakeRuff::new(GRAPH);
+    for (file, _) in GRAPH {
+        ruff.write_file(file, "");
+    }
+    let daemon = LiveProcess::spawn(ruff.command(&["daemon"]));
+    daemon.wait_for("Serving queries on .ruff-tools.sock!");
+
+    assert_eq!(
+        query(&ruff, &["affected-files", "c.py"]),
+        (true, "a.py\nb.py\nc.py\ntests/test_a.py\n".to_string())
+    );
+    assert_eq!(
+        query(&ruff, &["dependents", "a.py"]),
+        (true, "c.py\ntests/test_a.py\n".to_string())
+    );
+    assert_eq!(
+        query(&ruff, &["dependencies", "./a.py"]),
+        (true, "b.py\n".to_string())
+    );
+    assert_eq!(
+        query(&ruff, &["path-between", "tests/test_a.py", "c.py"]),
+        (
+            true,
+            "tests/test_a.py -> a.py -> b.py -> c.py\n".to_string()
+        )
+    );
+    assert_eq!(
+        query(&ruff, &["path-between", "a.py", "tests/test_a.py"]),
+        (true, "No import path\n".to_string())
+    );
+    assert_eq!(
+        query(&ruff, &["cycles", "b.py"]),
+        (true, "a.py -> b.py -> c.py\n".to_string())
+    );
+    assert_eq!(
+        query(&ruff, &["--json", "dependencies", "b.py"]),
+        (true, "[\"c.py\"]\n".to_string())
+    );
+    assert_eq!(
+        query(&ruff, &["dependents", "z.py"]),
+        (false, "unknown file 'z.py'\n".to_string())
+    );
+
+    // the graph stays up to date with changes
+    ruff.set_graph(&[
+        ("a.py", &["b.py"]),
+        ("b.py", &[]),
+        ("c.py", &["a.py"]),
+        ("tests/test_a.py", &["a.py"]),
+    ]);
+    ruff.write_file("b.py", "x = 1\n");
+    let start = Instant::now();
+    while query(&ruff, &["cycles", "b.py"]) != (true, "".to_string()) {
+        assert!(start.elapsed() < Duration::from_secs(30));
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn test_query_without_daemon() {
+    let ruff = FakeRuff::new(GRAPH);
+    let (success, output) = query(&ruff, &["dependents", "a.py"]);
+    assert!(!success);
+    assert!(output.starts_with("couldn't connect to .ruff-tools.sock"));
+}
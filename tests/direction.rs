@@ -0,0 +1,63 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::{FakeRuff, LiveProcess};
+
+/// a.py -> b.py -> c.py -> a.py, with a chord c.py -> a.py that's only a shortcut
+/// when followed in the import direction
+const GRAPH: &[(&str, &[&str])] = &[
+    ("a.py", &["b.py"]),
+    ("b.py", &["c.py"]),
+    ("c.py", &["d.py", "a.py"]),
+    ("d.py", &["a.py"]),
+];
+
+#[test]
+fn test_detect_cycles_uses_dependencies() {
+    let ruff = FakeRuff::new(GRAPH);
+    let output = ruff.command(&["detect-cycles"]).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(ruff.invocations(), ["analyze graph --preview"]);
+    assert!(stdout.lines().any(|l| l == "a.py -> b.py -> c.py"));
+    assert!(!stdout.lines().any(|l| l == "a.py -> c.py -> b.py"));
+}
+
+#[test]
+fn test_minimize_cycles_uses_dependencies() {
+    let ruff = FakeRuff::new(GRAPH);
+    let results = ruff.write_file(
+        "pylint.txt",
+        "a.py:1:0: R0401: Cyclic import(a -> b -> c -> d) (cyclic-import)\n",
+    );
+    let output = ruff
+        .command(&["minimize-cycles", "--cycle-results-file"])
+        .arg(results)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(ruff.invocations(), ["analyze graph --preview"]);
+    assert_eq!(stdout.lines().next(), Some("a -> b -> c"));
+}
+
+#[test]
+fn test_live_uses_both_directions() {
+    let ruff = FakeRuff::new(GRAPH);
+    ruff.write_file("a.py", "import b\n");
+    let live = LiveProcess::spawn(ruff.command(&["live", "--", "true"]));
+
+    live.wait_for("Listening!");
+    ruff.write_file("a.py", "import b\nimport c\n");
+    live.wait_for("COMPLETED RUN!");
+
+    assert_eq!(
+        ruff.invocations()[..3],
+        [
+            "analyze graph --preview --direction dependents",
+            "analyze graph --preview",
+            "analyze graph --preview a.py",
+        ]
+    );
+}
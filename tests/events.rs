@@ -0,0 +1,131 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::{FakeRuff, LiveProcess};
+use serde_json::Value;
+use std::io::{BufRead, BufReader};
+use std::os::unix::net::UnixStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const GRAPH: &[(&str, &[&str])] = &[
+    ("a.py", &["b.py"]),
+    ("b.py", &[]),
+    ("tests/test_a.py", &["a.py"]),
+];
+
+#[test]
+fn test_ndjson_on_stdout() {
+    let ruff = FakeRuff::new(GRAPH);
+    let output = ruff
+        .command(&[
+            "live",
+            "--events",
+            "ndjson",
+            "--once",
+            "--changed",
+            "b.py",
+            "--paths",
+            "tests",
+            "--",
+            "echo",
+        ])
+        .output()
+        .unwrap();
+
+    // every line of stdout is an event; everything else went to stderr
+    let events = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str::<Value>(l).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events.iter().map(|e| &e["event"]).collect::<Vec<_>>(),
+        [
+            "graph_updated",
+            "change",
+            "affected",
+            "command_started",
+            "command_finished"
+        ]
+    );
+    assert_eq!(events[0]["files"], 3);
+    assert_eq!(events[0]["edges"], 2);
+    assert_eq!(events[1]["paths"][0], "b.py");
+    assert_eq!(events[2]["files"][0], "tests/test_a.py");
+    assert_eq!(events[3]["command"][0], "echo");
+    assert_eq!(events[4]["exit_code"], 0);
+    assert!(events[4]["duration_ms"].is_u64());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Transitively affected files: tests/test_a.py"));
+    // the command's own output
+    assert!(stderr.lines().any(|l| l == "tests/test_a.py"));
+}
+
+#[test]
+fn test_ndjson_on_socket() {
+    let ruff = FakeRuff::new(GRAPH);
+    for (file, _) in GRAPH {
+        ruff.write_file(file, "");
+    }
+    let socket = ruff.project_dir().join("../events.sock");
+    let live = LiveProcess::spawn(ruff.command(&[
+        "live",
+        "--events",
+        "ndjson",
+        "--events-socket",
+        socket.to_str().unwrap(),
+        "--",
+        "true",
+    ]));
+    live.wait_for("Listening!");
+
+    let start = Instant::now();
+    let stream = loop {
+        match UnixStream::connect(&socket) {
+            Ok(stream) => break stream,
+            Err(e) => {
+                assert!(start.elapsed() < Duration::from_secs(30), "{}", e);
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    };
+    // the accepting thread may not have registered the client yet
+    thread::sleep(Duration::from_millis(100));
+
+    ruff.write_file("a.py", "x = 1\n");
+    let mut lines = BufReader::new(stream).lines();
+    let events = (&mut lines)
+        .map(|l| serde_json::from_str::<Value>(&l.unwrap()).unwrap())
+        .take_while(|e| e["event"] != "command_finished")
+        .collect::<Vec<_>>();
+    assert_eq!(events[0]["event"], "change");
+    assert!(events
+        .iter()
+        .any(|e| e["event"] == "affected" && e["files"].as_array().unwrap().len() == 2));
+    // human output still goes to stdout
+    live.wait_for("COMPLETED RUN!");
+}
+
+#[test]
+fn test_socket_bind_failure() {
+    let ruff = FakeRuff::new(GRAPH);
+    let socket = ruff.project_dir().join("missing/events.sock");
+    let output = ruff
+        .command(&[
+            "live",
+            "--events",
+            "ndjson",
+            "--events-socket",
+            socket.to_str().unwrap(),
+            "--",
+            "true",
+        ])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!String::from_utf8(output.stderr).unwrap().is_empty());
+}
@@ -0,0 +1,21 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::FakeRuff;
+
+#[test]
+fn test_export_write_failure() {
+    let ruff = FakeRuff::new(&[("a.py", &["b.py"]), ("b.py", &[])]);
+    let output = ruff
+        .command(&["export", "--output", "missing/graph.dot"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with("couldn't write missing/graph.dot: "),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,102 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::FakeRuff;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::process::Command;
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+        .status;
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+/// Commits the graph `ruff` serves at this revision
+fn commit_graph(ruff: &FakeRuff, graph: Value) {
+    ruff.write_file("ruff-graph.json", &graph.to_string());
+    git(&ruff.project_dir(), &["add", "-A"]);
+    git(&ruff.project_dir(), &["commit", "-q", "-m", "graph"]);
+}
+
+#[test]
+fn test_graph_diff_between_revisions() {
+    let ruff = FakeRuff::new(&[]);
+    git(&ruff.project_dir(), &["init", "-q"]);
+    commit_graph(
+        &ruff,
+        json!({"src/pkg/a.py": ["src/pkg/b.py"], "src/pkg/b.py": ["src/pkg/c.py"], "src/pkg/c.py": []}),
+    );
+    commit_graph(
+        &ruff,
+        json!({"src/pkg/a.py": ["src/pkg/b.py"], "src/pkg/b.py": ["src/pkg/a.py"]}),
+    );
+
+    let output = ruff
+        .command(&["graph-diff", "HEAD~1", "HEAD", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let diff: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        diff,
+        json!({
+            "added_modules": [],
+            "removed_modules": ["pkg.c"],
+            "added_imports": [{"from": "pkg.b", "to": "pkg.a"}],
+            "removed_imports": [{"from": "pkg.b", "to": "pkg.c"}],
+            "new_cycles": [["pkg.a", "pkg.b"]],
+            "resolved_cycles": [],
+        })
+    );
+    assert_eq!(ruff.invocations().len(), 2);
+
+    // the temporary checkouts are cleaned up again
+    let output = Command::new("git")
+        .args(["worktree", "list"])
+        .current_dir(ruff.project_dir())
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 1);
+}
+
+#[test]
+fn test_graph_diff_unknown_revision() {
+    let ruff = FakeRuff::new(&[]);
+    git(&ruff.project_dir(), &["init", "-q"]);
+    commit_graph(&ruff, json!({}));
+
+    let output = ruff
+        .command(&["graph-diff", "HEAD", "does-not-exist"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("git failed"));
+}
+
+#[test]
+fn test_graph_diff_similar_branch_names() {
+    let ruff = FakeRuff::new(&[]);
+    git(&ruff.project_dir(), &["init", "-q"]);
+    commit_graph(&ruff, json!({"a.py": [], "b.py": []}));
+    git(&ruff.project_dir(), &["branch", "feature/x"]);
+    commit_graph(&ruff, json!({"a.py": ["b.py"], "b.py": []}));
+    git(&ruff.project_dir(), &["branch", "feature-x"]);
+
+    // both sanitize to the same name, but each gets its own checkout
+    let output = ruff
+        .command(&["graph-diff", "feature/x", "feature-x", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let diff: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(diff["added_imports"], json!([{"from": "a", "to": "b"}]));
+}
@@ -0,0 +1,98 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::FakeRuff;
+use serde_json::Value;
+
+const GRAPH: &[(&str, &[&str])] = &[("a.py", &["b.py"]), ("b.py", &["a.py"])];
+
+fn write_sources(ruff: &FakeRuff) {
+    ruff.write_file("a.py", "import os\n\nimport b\n");
+    ruff.write_file("b.py", "def f():\n    from a import x\n");
+}
+
+#[test]
+fn test_detect_cycles_import_lines() {
+    let ruff = FakeRuff::new(GRAPH);
+    write_sources(&ruff);
+    let output = ruff
+        .command(&["detect-cycles", "--runtime-only", "false"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(
+        lines[..3],
+        ["a.py -> b.py", "    a.py:3 -> b.py", "    b.py:2 -> a.py"]
+    );
+
+    let output = ruff
+        .command(&[
+            "detect-cycles",
+            "--format",
+            "sarif",
+            "--runtime-only",
+            "false",
+        ])
+        .output()
+        .unwrap();
+    let log: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let result = &log["runs"][0]["results"][0];
+    assert_eq!(
+        result["locations"][0]["physicalLocation"]["region"]["startLine"],
+        3
+    );
+    assert_eq!(
+        result["relatedLocations"][0]["physicalLocation"]["region"]["startLine"],
+        2
+    );
+}
+
+#[test]
+fn test_detect_cycles_runtime_only() {
+    let ruff = FakeRuff::new(&[
+        ("a.py", &["b.py"]),
+        ("b.py", &["a.py"]),
+        ("c.py", &["d.py"]),
+        ("d.py", &["c.py"]),
+    ]);
+    write_sources(&ruff);
+    ruff.write_file("c.py", "import d\n");
+    ruff.write_file(
+        "d.py",
+        "from typing import TYPE_CHECKING\nif TYPE_CHECKING:\n    import c\n",
+    );
+    let output = ruff.command(&["detect-cycles"]).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("# cycles          : 0"), "{}", stdout);
+
+    let output = ruff
+        .command(&["detect-cycles", "--runtime-only", "false"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("# cycles          : 2"), "{}", stdout);
+}
+
+#[test]
+fn test_minimize_cycles_import_lines() {
+    let ruff = FakeRuff::new(GRAPH);
+    write_sources(&ruff);
+    let results = ruff.write_file(
+        "pylint.txt",
+        "a.py:1:0: R0401: Cyclic import(a -> b) (cyclic-import)\n",
+    );
+    let output = ruff
+        .command(&["minimize-cycles", "--cycle-results-file"])
+        .arg(results)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(
+        lines[..3],
+        ["a -> b", "    a.py:3 -> b.py", "    b.py:2 -> a.py"]
+    );
+}
@@ -0,0 +1,334 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::{FakeRuff, LiveProcess};
+use std::fs;
+
+const GRAPH: &[(&str, &[&str])] = &[
+    ("a.py", &["b.py"]),
+    ("b.py", &[]),
+    ("tests/test_a.py", &["a.py"]),
+];
+
+fn affected_files(line: &str) -> Vec<&str> {
+    let mut files = line
+        .strip_prefix("Transitively affected files: ")
+        .unwrap()
+        .split(", ")
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+fn start(ruff: &FakeRuff) -> LiveProcess {
+    for (file, _) in GRAPH {
+        ruff.write_file(file, "");
+    }
+    let live = LiveProcess::spawn(ruff.command(&["live", "--", "true"]));
+    live.wait_for("Listening!");
+    live
+}
+
+#[test]
+fn test_rename_moves_node() {
+    let ruff = FakeRuff::new(GRAPH);
+    let live = start(&ruff);
+
+    // a.py's import of b no longer resolves once b.py is moved
+    ruff.set_graph(&[("a.py", &[]), ("c.py", &[]), ("tests/test_a.py", &["a.py"])]);
+    let project = ruff.project_dir();
+    fs::rename(project.join("b.py"), project.join("c.py")).unwrap();
+    live.wait_for("Renamed path: b.py -> c.py");
+    let lines = live.wait_for("Transitively affected files");
+    assert_eq!(
+        affected_files(lines.last().unwrap()),
+        ["a.py", "c.py", "tests/test_a.py"]
+    );
+    live.wait_for("COMPLETED RUN!");
+
+    // the old edge is gone, so a change to the moved file only affects itself
+    ruff.write_file("c.py", "x = 1\n");
+    live.wait_for("Changed paths: c.py");
+    let lines = live.wait_for("Transitively affected files");
+    assert_eq!(affected_files(lines.last().unwrap()), ["c.py"]);
+}
+
+#[test]
+fn test_move_out_of_tree_removes_node() {
+    let ruff = FakeRuff::new(GRAPH);
+    let live = start(&ruff);
+
+    ruff.set_graph(&[("a.py", &[]), ("tests/test_a.py", &["a.py"])]);
+    let project = ruff.project_dir();
+    fs::rename(project.join("b.py"), project.join("../b.py")).unwrap();
+    live.wait_for("Removed paths: b.py");
+    let lines = live.wait_for("Transitively affected files");
+    assert_eq!(
+        affected_files(lines.last().unwrap()),
+        ["a.py", "tests/test_a.py"]
+    );
+}
+
+#[test]
+fn test_move_directory() {
+    let ruff = FakeRuff::new(&[
+        ("main.py", &["pkg/a.py"]),
+        ("pkg/a.py", &["pkg/b.py"]),
+        ("pkg/b.py", &[]),
+        ("tests/test_main.py", &["main.py"]),
+    ]);
+    for file in ["main.py", "pkg/a.py", "pkg/b.py", "tests/test_main.py"] {
+        ruff.write_file(file, "");
+    }
+    let live = LiveProcess::spawn(ruff.command(&["live", "--", "true"]));
+    live.wait_for("Listening!");
+
+    // main.py's import of pkg.a no longer resolves once the package is moved
+    ruff.set_graph(&[
+        ("main.py", &[]),
+        ("pkg2/a.py", &["pkg2/b.py"]),
+        ("pkg2/b.py", &[]),
+        ("tests/test_main.py", &["main.py"]),
+    ]);
+    let project = ruff.project_dir();
+    fs::rename(project.join("pkg"), project.join("pkg2")).unwrap();
+    live.wait_for("Renamed path: pkg -> pkg2");
+    let lines = live.wait_for("Transitively affected files");
+    assert_eq!(
+        affected_files(lines.last().unwrap()),
+        ["main.py", "pkg2/a.py", "pkg2/b.py", "tests/test_main.py"]
+    );
+    live.wait_for("COMPLETED RUN!");
+    // the old importer was re-analyzed along with the moved files
+    let update = ruff.invocations().pop().unwrap();
+    for file in ["main.py", "pkg2/a.py", "pkg2/b.py"] {
+        assert!(update.contains(file), "{}", update);
+    }
+
+    // the old edge is gone, so a change to a moved file no longer affects main.py
+    ruff.write_file("pkg2/b.py", "x = 1\n");
+    let lines = live.wait_for("Transitively affected files");
+    assert_eq!(
+        affected_files(lines.last().unwrap()),
+        ["pkg2/a.py", "pkg2/b.py"]
+    );
+}
+
+#[test]
+fn test_once_changed() {
+    let ruff = FakeRuff::new(GRAPH);
+    let output = ruff
+        .command(&["live", "--once", "--changed", "./b.py", "--", "echo"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let line = stdout
+        .lines()
+        .find(|l| l.starts_with("Transitively affected files"))
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(stdout.contains("Changed paths: b.py"));
+    assert_eq!(affected_files(line), ["a.py", "b.py", "tests/test_a.py"]);
+    // the graph from startup is used as is
+    assert_eq!(ruff.invocations().len(), 2);
+}
+
+#[test]
+fn test_once_run_on_start_exit_code() {
+    let ruff = FakeRuff::new(GRAPH);
+    let output = ruff
+        .command(&[
+            "live",
+            "--once",
+            "--run-on-start",
+            "--paths",
+            "tests",
+            "--",
+            "false",
+        ])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let line = stdout
+        .lines()
+        .find(|l| l.starts_with("Transitively affected files"))
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(affected_files(line), ["tests/test_a.py"]);
+}
+
+#[test]
+fn test_once_nothing_to_do() {
+    let ruff = FakeRuff::new(GRAPH);
+    let output = ruff
+        .command(&["live", "--once", "--changed", "unknown.py", "--", "false"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("Nothing to do!"));
+}
+
+#[test]
+fn test_once_invalid_options() {
+    let ruff = FakeRuff::new(GRAPH);
+    for args in [
+        &["live", "--once", "--", "true"][..],
+        &[
+            "live",
+            "--once",
+            "--run-on-start",
+            "--changed",
+            "b.py",
+            "--",
+            "true",
+        ],
+    ] {
+        let output = ruff.command(args).output().unwrap();
+        assert!(!output.status.success(), "{:?}", args);
+        assert!(ruff.invocations().is_empty());
+    }
+
+    let output = ruff
+        .command(&["live", "--once", "--run-on-start"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("a command to run must be provided"));
+}
+
+#[test]
+fn test_keys() {
+    let ruff = FakeRuff::new(GRAPH);
+    let mut live = start(&ruff);
+
+    live.send("a");
+    let lines = live.wait_for("Transitively affected files");
+    assert_eq!(
+        affected_files(lines.last().unwrap()),
+        ["a.py", "b.py", "tests/test_a.py"]
+    );
+    live.wait_for("COMPLETED RUN!");
+
+    live.send("");
+    live.wait_for("Repeating last run");
+    live.wait_for("COMPLETED RUN!");
+
+    live.send("f");
+    live.wait_for("No failures to re-run!");
+
+    live.send("p");
+    live.wait_for("Paused!");
+    ruff.write_file("tests/test_a.py", "x = 1\n");
+    live.wait_for("affected files will run on resume");
+    live.send("p");
+    live.wait_for("Resumed!");
+    let lines = live.wait_for("Transitively affected files");
+    assert_eq!(affected_files(lines.last().unwrap()), ["tests/test_a.py"]);
+    live.wait_for("COMPLETED RUN!");
+
+    live.send("q");
+    assert!(live.wait_for_exit().success());
+}
+
+#[test]
+fn test_command_has_no_stdin() {
+    let ruff = FakeRuff::new(GRAPH);
+    for (file, _) in GRAPH {
+        ruff.write_file(file, "");
+    }
+    ruff.write_file(
+        "read_stdin.sh",
+        "if read line; then echo \"READ $line\"; else echo \"NO STDIN\"; fi\n",
+    );
+    let mut live = LiveProcess::spawn(ruff.command(&["live", "--", "sh", "read_stdin.sh"]));
+    live.wait_for("Listening!");
+
+    live.send("a");
+    live.wait_for("NO STDIN");
+    live.wait_for("COMPLETED RUN!");
+    // keys still reach live mode rather than the command
+    live.send("q");
+    assert!(live.wait_for_exit().success());
+}
+
+#[test]
+fn test_rerun_failures_key() {
+    let ruff = FakeRuff::new(GRAPH);
+    for (file, _) in GRAPH {
+        ruff.write_file(file, "");
+    }
+    let mut live = LiveProcess::spawn(ruff.command(&["live", "--", "false"]));
+    live.wait_for("Listening!");
+
+    live.send("f");
+    live.wait_for("No failures to re-run!");
+    live.send("a");
+    live.wait_for("COMPLETED RUN!");
+    // without a report, everything in a failed run is failing
+    live.send("f");
+    live.wait_for("Re-running failures: a.py, b.py, tests/test_a.py");
+    live.wait_for("COMPLETED RUN!");
+
+    // they aren't added to later runs, and only the last run's files are failing
+    ruff.write_file("tests/test_a.py", "x = 1\n");
+    let lines = live.wait_for("Transitively affected files");
+    assert_eq!(affected_files(lines.last().unwrap()), ["tests/test_a.py"]);
+    live.wait_for("COMPLETED RUN!");
+    live.send("f");
+    live.wait_for("Re-running failures: tests/test_a.py");
+}
+
+#[test]
+fn test_junit_failures_run_first() {
+    let ruff = FakeRuff::new(&[
+        ("a.py", &[]),
+        ("tests/test_a.py", &["a.py"]),
+        ("tests/test_b.py", &[]),
+    ]);
+    ruff.write_file("a.py", "");
+    ruff.write_file("tests/test_a.py", "");
+    ruff.write_file("tests/test_b.py", "");
+    ruff.write_file(
+        "fake_pytest.sh",
+        r#"cat > report.xml <<EOF
+<testsuites><testsuite>
+<testcase classname="tests.test_a" name="test_fails"><failure message="nope" /></testcase>
+<testcase classname="tests.test_b" name="test_ok" />
+</testsuite></testsuites>
+EOF
+exit 1
+"#,
+    );
+    let mut live = LiveProcess::spawn(ruff.command(&[
+        "live",
+        "--junit-xml",
+        "report.xml",
+        "--",
+        "sh",
+        "fake_pytest.sh",
+    ]));
+    live.wait_for("Listening!");
+
+    live.send("a");
+    live.wait_for("Failing files: tests/test_a.py");
+
+    // an unrelated change still re-runs the failure, first
+    ruff.write_file("tests/test_b.py", "x = 1\n");
+    let lines = live.wait_for("Transitively affected files");
+    assert_eq!(
+        lines.last().unwrap(),
+        "Transitively affected files: tests/test_a.py, tests/test_b.py"
+    );
+    live.wait_for("COMPLETED RUN!");
+
+    live.send("f");
+    live.wait_for("Re-running failures: tests/test_a.py");
+}
@@ -0,0 +1,143 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::FakeRuff;
+
+const GRAPH: &[(&str, &[&str])] = &[
+    ("a.py", &["b.py"]),
+    ("b.py", &["c.py"]),
+    ("c.py", &["a.py"]),
+    ("d.py", &[]),
+];
+
+#[test]
+fn test_validate_cycles() {
+    let ruff = FakeRuff::new(GRAPH);
+    let results = ruff.write_file(
+        "cycles.txt",
+        "a -> b -> c\nb -> d\nc -> stale\nnot a cycle -> x\n",
+    );
+    let output = ruff
+        .command(&["minimize-cycles", "--cycle-results-file"])
+        .arg(&results)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    // only the confirmed cycle is minimized
+    assert!(stdout.starts_with("a -> b -> c\n"), "{}", stdout);
+    assert!(stdout.contains("Post-minimization\n# cycles          : 1\n"));
+    let validation = stdout
+        .split("Validation against ruff's graph\n")
+        .nth(1)
+        .unwrap();
+    assert_eq!(
+        validation,
+        "\
+confirmed cycles  : 1
+false positives   : 2
+disagreeing edges : 4
+b -> d
+    no import b -> d
+    no import d -> b
+c -> stale
+    no import c -> stale (stale isn't in ruff's graph)
+    no import stale -> c (stale isn't in ruff's graph)
+"
+    );
+    assert_eq!(
+        stderr,
+        format!(
+            "{}: line 4: expected a cycle like 'a -> b -> c', got 'not a cycle -> x'\n",
+            results.display()
+        )
+    );
+}
+
+#[test]
+fn test_compare_cycles() {
+    let ruff = FakeRuff::new(&[
+        ("a.py", &["b.py"]),
+        ("b.py", &["c.py"]),
+        ("c.py", &["a.py"]),
+        ("d.py", &["e.py"]),
+        ("e.py", &["d.py"]),
+    ]);
+    let results = ruff.write_file(
+        "pylint.txt",
+        "\
+a.py:1:0: R0401: Cyclic import (b -> c -> a) (cyclic-import)
+a.py:1:0: R0401: Cyclic import (a -> old) (cyclic-import)
+",
+    );
+    let output = ruff
+        .command(&["compare-cycles", "--pylint-results"])
+        .arg(&results)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "\
+Found by both: 1
+    a -> b -> c
+Only found by ruff-tools: 1
+    d -> e
+Only found by pylint: 0
+Found by pylint, with imports that aren't in ruff's graph or don't run at import time: 1
+    a -> old
+"
+    );
+
+    let output = ruff
+        .command(&["compare-cycles", "--json", "--pylint-results"])
+        .arg(&results)
+        .output()
+        .unwrap();
+    let comparison: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(comparison["common"], serde_json::json!([["a", "b", "c"]]));
+    assert_eq!(comparison["only_pylint"], serde_json::json!([]));
+}
+
+#[test]
+fn test_compare_cycles_runtime_only() {
+    let ruff = FakeRuff::new(&[("a.py", &["b.py"]), ("b.py", &["a.py"])]);
+    ruff.write_file("a.py", "import b\n");
+    ruff.write_file("b.py", "def f():\n    import a\n");
+    let results = ruff.write_file(
+        "pylint.txt",
+        "a.py:1:0: R0401: Cyclic import (a -> b) (cyclic-import)\n",
+    );
+
+    // like detect-cycles, the import inside a function is left out by default
+    let output = ruff
+        .command(&["compare-cycles", "--json", "--pylint-results"])
+        .arg(&results)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let comparison: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(comparison["common"], serde_json::json!([]));
+    assert_eq!(
+        comparison["false_positives"],
+        serde_json::json!([["a", "b"]])
+    );
+
+    let output = ruff
+        .command(&[
+            "compare-cycles",
+            "--runtime-only",
+            "false",
+            "--json",
+            "--pylint-results",
+        ])
+        .arg(&results)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let comparison: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(comparison["common"], serde_json::json!([["a", "b"]]));
+}
@@ -0,0 +1,47 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::FakeRuff;
+use serde_json::Value;
+
+#[test]
+fn test_detect_cycles_sarif() {
+    let ruff = FakeRuff::new(&[
+        ("a.py", &["b.py"]),
+        ("b.py", &["a.py"]),
+        ("c.py", &["d.py"]),
+        ("d.py", &["e.py"]),
+        ("e.py", &["c.py"]),
+    ]);
+    let output = ruff
+        .command(&["detect-cycles", "--format", "sarif"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let log: Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(log["version"], "2.1.0");
+    let run = &log["runs"][0];
+    assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "cyclic-import");
+    let messages = run["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["message"]["text"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        [
+            "Import cycle: a.py -> b.py -> a.py",
+            "Import cycle: c.py -> d.py -> e.py -> c.py",
+        ]
+    );
+    assert_eq!(
+        run["results"][1]["relatedLocations"]
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+}
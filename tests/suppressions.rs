@@ -0,0 +1,57 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::FakeRuff;
+use serde_json::Value;
+
+fn fake_ruff() -> FakeRuff {
+    let ruff = FakeRuff::new(&[
+        ("a.py", &["b.py"]),
+        ("b.py", &["a.py", "c.py"]),
+        ("c.py", &["d.py"]),
+        ("d.py", &["c.py"]),
+    ]);
+    ruff.write_file("a.py", "import b\n");
+    ruff.write_file(
+        "b.py",
+        "import a  # ruff-tools: ignore-cycle\nimport c  # noqa: cyclic-import\n",
+    );
+    ruff.write_file("c.py", "import d\n");
+    ruff.write_file("d.py", "import c\n");
+    ruff
+}
+
+#[test]
+fn test_suppressed_imports_break_cycles() {
+    let ruff = fake_ruff();
+    let output = ruff.command(&["detect-cycles"]).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines[0], "c.py -> d.py");
+    assert!(stdout.contains("# cycles          : 1"), "{}", stdout);
+    assert!(
+        stdout.ends_with(
+            "Unused cycle suppressions (the imports aren't part of any cycle):\nb.py:2\n"
+        ),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_unused_suppressions_sarif() {
+    let ruff = fake_ruff();
+    let output = ruff
+        .command(&["detect-cycles", "--format", "sarif"])
+        .output()
+        .unwrap();
+    let log: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let results = log["runs"][0]["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0]["ruleId"], "cyclic-import");
+    assert_eq!(results[1]["ruleId"], "unused-cycle-suppression");
+    let location = &results[1]["locations"][0]["physicalLocation"];
+    assert_eq!(location["artifactLocation"]["uri"], "b.py");
+    assert_eq!(location["region"]["startLine"], 2);
+}